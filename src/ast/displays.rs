@@ -1,7 +1,7 @@
 use std::fmt;
 use std::fmt::{Display, Formatter};
 
-use crate::ast::{Module, VariableDef};
+use crate::ast::{Import, Module, VariableDef};
 use crate::ast::expr::{Expr, FunctionCallExpr, FunctionDef, IfExpr, PartialExpr, TupleDef, VariableExpr};
 use crate::ast::states::AstState;
 use crate::ast::types::VariableType;
@@ -9,21 +9,30 @@ use crate::ast::utils::str_from_iter;
 
 impl<S: AstState> Display for Module<S> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for i in &self.imports {
+            writeln!(f, "{}", i)?
+        }
         for vd in &self.variables {
-            writeln!(f, "{}{}", vd.name, vd.variable)?
+            writeln!(f, "{}{}{}", if vd.public { "pub " } else { "" }, vd.name, vd.variable)?
         }
         Ok(())
     }
 }
 
-impl Display for VariableDef {
+impl Display for Import {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "import {}.{{{}}}", self.module, str_from_iter(self.names.iter(), ","))
+    }
+}
+
+impl<S: AstState> Display for VariableDef<S> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, ":{}= {}", self.v_type, self.value)?;
         Ok(())
     }
 }
 
-impl Display for Expr {
+impl<S: AstState> Display for Expr<S> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         for (expr, op) in self.exprs.iter().zip(&self.operands) {
             write!(f, "{} {:?} ", expr, op)?
@@ -35,7 +44,7 @@ impl Display for Expr {
     }
 }
 
-impl Display for PartialExpr {
+impl<S: AstState> Display for PartialExpr<S> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             PartialExpr::Block(_) => write!(f, "BLOCK"),
@@ -48,7 +57,7 @@ impl Display for PartialExpr {
     }
 }
 
-impl Display for IfExpr {
+impl<S: AstState> Display for IfExpr<S> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "IF({}){}", self.cond_expr, self.main_branch)?;
         match &self.else_branch {
@@ -58,32 +67,35 @@ impl Display for IfExpr {
     }
 }
 
-impl Display for FunctionCallExpr {
+impl<S: AstState> Display for FunctionCallExpr<S> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "CALL {}({})", self.name, str_from_iter(self.params.iter(), ","))
+        let call = if self.tail { "TAILCALL" } else { "CALL" };
+        write!(f, "{} {}({})", call, self.name, str_from_iter(self.params.iter(), ","))
     }
 }
 
-impl Display for VariableExpr {
+impl<S: AstState> Display for VariableExpr<S> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            VariableExpr::Variable(name) => write!(f, "{}", name),
+            VariableExpr::Variable { name, .. } => write!(f, "{}", name),
             VariableExpr::Constant(cv) => write!(f, "{:?}", cv)
         }
     }
 }
 
-impl Display for FunctionDef {
+impl<S: AstState> Display for FunctionDef<S> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "<{}><{}>:{}->{}",
+        write!(f, "{}{}<{}><{}>:{}->{}",
+               if self.pure { "pure " } else { "" },
+               if self.memo { "memo " } else { "" },
                str_from_iter(self.parameters.iter().map(|(name, vt)| format!("{}:{}", name, vt)), ","),
-               str_from_iter(self.closure.iter().map(|(name, m)| format!("{}{}", if *m { "mut " } else { "" }, name)), ","),
+               str_from_iter(self.closure.iter().map(|c| format!("{}{}", if c.mutable { "mut " } else { "" }, c.name)), ","),
                self.return_type,
                self.expr)
     }
 }
 
-impl<'s> Display for TupleDef {
+impl<S: AstState> Display for TupleDef<S> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "({})", str_from_iter(self.items.iter(), ","))
     }
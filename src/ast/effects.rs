@@ -0,0 +1,50 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::ops::BitOr;
+
+/// Effects of calling a function, inferred by `verify`. A function without effects is pure: its result depends
+/// only on its parameters, so its calls can be cached, reordered or run in parallel.
+/// Scripts can do I/O only by calling host functions, so I/O is one of the `host_calls`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Effects {
+    /// Captures a `mut` variable, which can be changed.
+    pub mutation: bool,
+    /// Calls a function provided by the host.
+    pub host_calls: bool,
+    /// Calls a function value, whose effects are not known when verifying: a parameter, a captured variable,
+    /// or a module variable, which is not defined by a lambda.
+    pub unknown_calls: bool,
+}
+
+impl Effects {
+    pub const PURE: Effects = Effects { mutation: false, host_calls: false, unknown_calls: false };
+    pub const HOST_CALL: Effects = Effects { mutation: false, host_calls: true, unknown_calls: false };
+    pub const UNKNOWN_CALL: Effects = Effects { mutation: false, host_calls: false, unknown_calls: true };
+
+    pub fn is_pure(&self) -> bool {
+        *self == Effects::PURE
+    }
+}
+
+impl BitOr for Effects {
+    type Output = Effects;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Effects {
+            mutation: self.mutation || rhs.mutation,
+            host_calls: self.host_calls || rhs.host_calls,
+            unknown_calls: self.unknown_calls || rhs.unknown_calls,
+        }
+    }
+}
+
+impl Display for Effects {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.is_pure() {
+            return write!(f, "pure");
+        }
+        let names = [(self.mutation, "mutation"), (self.host_calls, "host calls"), (self.unknown_calls, "unknown calls")];
+        let names: Vec<_> = names.iter().filter(|(set, _)| *set).map(|(_, name)| *name).collect();
+        write!(f, "{}", names.join(", "))
+    }
+}
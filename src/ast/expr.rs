@@ -1,61 +1,94 @@
+use std::rc::Rc;
+
+use crate::ast::location::Location;
+use crate::ast::states::AstState;
+use crate::ast::symbol::Symbol;
 use crate::ast::variable::ConstantValue;
 use crate::ast::types::VariableType;
 
 #[derive(Clone)]
-pub struct Expr {
-    pub exprs: Vec<PartialExpr>,
+pub struct Expr<S: AstState> {
+    pub exprs: Vec<PartialExpr<S>>,
     pub operands: Vec<Operand>,
+    pub location: Location,
+    pub v_type: S::Type,
 }
 
-#[derive(Debug,Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Operand {
     //TODO: more operands
     Plus,
     Minus,
     Lt,
+    Eq,
+    Ne,
 }
 
 #[derive(Clone)]
-pub enum PartialExpr {
+pub enum PartialExpr<S: AstState> {
     Block(BlockExpr),
-    If(IfExpr),
-    FunctionCall(FunctionCallExpr),
-    Variable(VariableExpr),
-    Lambda(FunctionDef),
-    Tuple(TupleDef),
+    If(IfExpr<S>),
+    FunctionCall(FunctionCallExpr<S>),
+    Variable(VariableExpr<S>),
+    Lambda(FunctionDef<S>),
+    Tuple(TupleDef<S>),
 }
 
 #[derive(Clone)]
 pub struct BlockExpr {}
 
 #[derive(Clone)]
-pub struct IfExpr {
-    pub cond_expr: Expr,
-    pub main_branch: Expr,
-    pub else_branch: Option<Expr>,
+pub struct IfExpr<S: AstState> {
+    pub cond_expr: Expr<S>,
+    pub main_branch: Expr<S>,
+    pub else_branch: Option<Expr<S>>,
+    pub v_type: S::Type,
 }
 
 #[derive(Clone)]
-pub struct FunctionCallExpr {
-    pub name: String,
-    pub params: Vec<Expr>,
+pub struct FunctionCallExpr<S: AstState> {
+    pub name: Symbol,
+    pub params: Vec<Expr<S>>,
+    pub location: Location,
+    pub binding: S::Binding,
+    pub v_type: S::Type,
+    /// The call is the last evaluated expression of a function. It reuses the frame of the function at runtime.
+    pub tail: bool,
 }
 
 #[derive(Clone)]
-pub enum VariableExpr {
-    Variable(String),
+pub enum VariableExpr<S: AstState> {
+    Variable { name: Symbol, location: Location, binding: S::Binding, v_type: S::Type },
     Constant(ConstantValue),
 }
 
 #[derive(Clone)]
-pub struct FunctionDef{
-    pub parameters: Vec<(String, VariableType)>,
-    pub closure: Vec<(String, bool)>,
+pub struct FunctionDef<S: AstState> {
+    pub parameters: Vec<(Symbol, VariableType)>,
+    pub closure: Vec<Capture<S>>,
     pub return_type: VariableType,
-    pub expr: Expr,
+    /// The body is shared with the function values created from the definition.
+    pub expr: Rc<Expr<S>>,
+    pub location: Location,
+    /// `memo <a:Int>:Int -> ...`: the function values cache their results, keyed by the parameter values.
+    pub memo: bool,
+    /// `pure <a:Int>:Int -> ...`: `verify` checks that the function has no effects.
+    pub pure: bool,
+    pub effects: S::Effects,
+    /// The body refers to `self_fn`, so it is created for each call. Set by `verify`.
+    pub uses_self: bool,
 }
 
+/// Variable captured to the closure of a function.
 #[derive(Clone)]
-pub struct TupleDef {
-    pub items: Vec<Expr>,
-}
\ No newline at end of file
+pub struct Capture<S: AstState> {
+    pub name: Symbol,
+    pub mutable: bool,
+    pub binding: S::Binding,
+}
+
+#[derive(Clone)]
+pub struct TupleDef<S: AstState> {
+    pub items: Vec<Expr<S>>,
+    pub v_type: S::Type,
+}
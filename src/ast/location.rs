@@ -0,0 +1,92 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+use crate::ast::expr::{Expr, PartialExpr, VariableExpr};
+use crate::ast::Module;
+use crate::ast::states::AstState;
+
+/// Position in the source code. Lines and columns start from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Location {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    /// Location of the start of `input`, which is the remaining part of the parsed source.
+    /// Only the distance to the end of the source is known, until the location is resolved.
+    pub(crate) fn pending(input: &str) -> Self {
+        Location { offset: input.len(), line: 0, column: 0 }
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Converts the pending locations of a parsed source to lines and columns.
+pub(crate) struct LineIndex<'a> {
+    source: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub(crate) fn new(source: &'a str) -> Self {
+        let line_starts = std::iter::once(0).chain(source.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        LineIndex { source, line_starts }
+    }
+
+    pub(crate) fn resolve(&self, location: &mut Location) {
+        let offset = self.source.len() - location.offset;
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        *location = Location {
+            offset,
+            line,
+            column: self.source[self.line_starts[line - 1]..offset].chars().count() + 1,
+        };
+    }
+}
+
+impl<S: AstState> Module<S> {
+    pub(crate) fn locations_mut(&mut self, f: &mut impl FnMut(&mut Location)) {
+        for i in &mut self.imports {
+            f(&mut i.location);
+        }
+        for v in &mut self.variables {
+            f(&mut v.location);
+            v.variable.value.locations_mut(f);
+        }
+    }
+}
+
+impl<S: AstState> Expr<S> {
+    pub(crate) fn locations_mut(&mut self, f: &mut impl FnMut(&mut Location)) {
+        f(&mut self.location);
+        for e in &mut self.exprs {
+            match e {
+                PartialExpr::Block(_) | PartialExpr::Variable(VariableExpr::Constant(_)) => (),
+                PartialExpr::Variable(VariableExpr::Variable { location, .. }) => f(location),
+                PartialExpr::If(e) => {
+                    e.cond_expr.locations_mut(f);
+                    e.main_branch.locations_mut(f);
+                    if let Some(eb) = &mut e.else_branch {
+                        eb.locations_mut(f)
+                    }
+                }
+                PartialExpr::FunctionCall(e) => {
+                    f(&mut e.location);
+                    e.params.iter_mut().for_each(|p| p.locations_mut(f))
+                }
+                PartialExpr::Lambda(fd) => {
+                    f(&mut fd.location);
+                    Rc::make_mut(&mut fd.expr).locations_mut(f)
+                }
+                PartialExpr::Tuple(t) => t.items.iter_mut().for_each(|i| i.locations_mut(f)),
+            }
+        }
+    }
+}
@@ -1,26 +1,46 @@
 use std::collections::HashSet;
 
 use crate::ast::expr::Expr;
+use crate::ast::location::Location;
 use crate::ast::states::AstState;
 use crate::ast::structs::StructDef;
+use crate::ast::symbol::{Symbol, SymbolTable};
 use crate::ast::types::VariableType;
-use crate::ast::variable::VariableName;
+use crate::ast::variable::{Link, VariableName};
 
 pub mod expr;
+pub mod location;
 pub mod types;
 pub mod states;
 pub mod displays;
+pub mod effects;
 pub mod structs;
+pub mod symbol;
 mod utils;
 pub mod variable;
 
 pub struct Module<S: AstState> {
-    pub variables: Vec<VariableName>,
+    pub imports: Vec<Import>,
+    pub variables: Vec<VariableName<S>>,
     pub structs: HashSet<StructDef>,
+    /// Variables used by the module, which are defined outside of it. Set by `verify`.
+    pub links: Vec<Link>,
+    /// Table of the names of the symbols of the module.
+    pub symbols: SymbolTable,
     pub _state: std::marker::PhantomData<S>,
 }
 
-pub struct VariableDef {
-    pub value: Expr,
+/// `import A.{fib, g}` makes the listed variables of module `A` visible without the `A::` prefix.
+pub struct Import {
+    pub module: Symbol,
+    pub names: Vec<Symbol>,
+    pub location: Location,
+}
+
+pub struct VariableDef<S: AstState> {
+    pub value: Expr<S>,
     pub v_type: VariableType,
+    /// Type variables of `v_type`, which the value compares for equality, so they can not be functions.
+    /// Filled by `verify`.
+    pub comparable: Vec<usize>,
 }
@@ -1,9 +1,29 @@
-pub trait AstState {}
+use crate::ast::effects::Effects;
+use crate::ast::types::VariableType;
+use crate::ast::variable::Binding;
 
+/// State of the AST. A verified AST stores the resolved type of every expression
+/// and the binding of every variable reference, and the effects of every function.
+pub trait AstState: Clone {
+    type Type: Clone;
+    type Binding: Clone;
+    type Effects: Clone;
+}
+
+#[derive(Clone)]
 pub struct Unverified;
 
+#[derive(Clone)]
 pub struct Verified;
 
-impl AstState for Unverified {}
+impl AstState for Unverified {
+    type Type = ();
+    type Binding = ();
+    type Effects = ();
+}
 
-impl AstState for Verified {}
\ No newline at end of file
+impl AstState for Verified {
+    type Type = VariableType;
+    type Binding = Binding;
+    type Effects = Effects;
+}
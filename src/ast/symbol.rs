@@ -0,0 +1,149 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use crate::ast::expr::{Expr, PartialExpr, VariableExpr};
+use crate::ast::Module;
+use crate::ast::states::Unverified;
+
+/// Interned identifier: the index of its name in a `SymbolTable`. Symbols are compared and hashed as integers,
+/// so only the symbols of the same table can be compared.
+#[derive(Clone)]
+pub struct Symbol {
+    id: u32,
+    table: SymbolTable,
+}
+
+/// Names of the symbols. A `RunEnv` shares its table with its modules and executions,
+/// and the names are freed with the last of them.
+#[derive(Clone, Default)]
+pub struct SymbolTable(Rc<RefCell<Names>>);
+
+#[derive(Default)]
+struct Names {
+    names: Vec<Rc<str>>,
+    ids: HashMap<Rc<str>, u32>,
+}
+
+impl SymbolTable {
+    /// The symbol of `name`. The name is stored only when it is interned the first time.
+    pub fn intern(&self, name: &str) -> Symbol {
+        let mut table = self.0.borrow_mut();
+        let id = match table.ids.get(name) {
+            Some(id) => *id,
+            None => {
+                let id = table.names.len() as u32;
+                let name: Rc<str> = Rc::from(name);
+                table.names.push(name.clone());
+                table.ids.insert(name, id);
+                id
+            }
+        };
+        Symbol { id, table: self.clone() }
+    }
+
+    /// The symbol of `name`, if it is already interned.
+    pub fn find(&self, name: &str) -> Option<Symbol> {
+        let id = *self.0.borrow().ids.get(name)?;
+        Some(Symbol { id, table: self.clone() })
+    }
+
+    pub fn same(&self, other: &SymbolTable) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Symbol {
+    pub fn name(&self) -> Rc<str> {
+        self.table.0.borrow().names[self.id as usize].clone()
+    }
+
+    /// The same name in the table `table`.
+    pub fn intern_into(&self, table: &SymbolTable) -> Symbol {
+        if self.table.same(table) {
+            self.clone()
+        } else {
+            table.intern(&self.name())
+        }
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        debug_assert!(self.table.same(&other.table), "symbols {} and {} are from different tables", self, other);
+        self.id == other.id
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state)
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.table.0.borrow().names[self.id as usize])
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&*self.name(), f)
+    }
+}
+
+impl Module<Unverified> {
+    /// Moves the symbols of the module to the table `table`.
+    pub(crate) fn intern_into(mut self, table: &SymbolTable) -> Self {
+        if !self.symbols.same(table) {
+            self.symbols_mut(&mut |s| *s = s.intern_into(table));
+            self.symbols = table.clone();
+        }
+        self
+    }
+
+    fn symbols_mut(&mut self, f: &mut impl FnMut(&mut Symbol)) {
+        for i in &mut self.imports {
+            f(&mut i.module);
+            i.names.iter_mut().for_each(&mut *f);
+        }
+        for v in &mut self.variables {
+            f(&mut v.name);
+            v.variable.value.symbols_mut(f);
+        }
+    }
+}
+
+impl Expr<Unverified> {
+    fn symbols_mut(&mut self, f: &mut impl FnMut(&mut Symbol)) {
+        for e in &mut self.exprs {
+            match e {
+                PartialExpr::Block(_) | PartialExpr::Variable(VariableExpr::Constant(_)) => (),
+                PartialExpr::Variable(VariableExpr::Variable { name, .. }) => f(name),
+                PartialExpr::If(e) => {
+                    e.cond_expr.symbols_mut(f);
+                    e.main_branch.symbols_mut(f);
+                    if let Some(eb) = &mut e.else_branch {
+                        eb.symbols_mut(f)
+                    }
+                }
+                PartialExpr::FunctionCall(e) => {
+                    f(&mut e.name);
+                    e.params.iter_mut().for_each(|p| p.symbols_mut(f))
+                }
+                PartialExpr::Lambda(fd) => {
+                    fd.parameters.iter_mut().for_each(|(name, _)| f(name));
+                    fd.closure.iter_mut().for_each(|c| f(&mut c.name));
+                    Rc::make_mut(&mut fd.expr).symbols_mut(f)
+                }
+                PartialExpr::Tuple(t) => t.items.iter_mut().for_each(|i| i.symbols_mut(f)),
+            }
+        }
+    }
+}
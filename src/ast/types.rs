@@ -2,13 +2,16 @@ use std::fmt;
 use std::fmt::{Display, Formatter};
 
 use crate::ast::expr::FunctionDef;
+use crate::ast::states::AstState;
 use crate::ast::utils::str_from_iter;
 
-#[derive(PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum TypeInfo {
     Struct(String),
     Tuple(Vec<TypeInfo>),
     Function(FuncType),
+    /// Type variable of the type inference. Variables left in a verified type are generic.
+    Var(usize),
     Unknown,
 }
 
@@ -16,6 +19,32 @@ impl TypeInfo {
     pub fn empty() -> Self {
         TypeInfo::Tuple(Vec::new())
     }
+
+    /// Replaces every type variable with the result of `f`.
+    pub(crate) fn substitute(&self, f: &mut impl FnMut(usize) -> TypeInfo) -> TypeInfo {
+        match self {
+            TypeInfo::Var(id) => f(*id),
+            TypeInfo::Tuple(v) => TypeInfo::Tuple(v.iter().map(|t| t.substitute(f)).collect()),
+            TypeInfo::Function(func) => TypeInfo::Function(FuncType {
+                params: func.params.iter().map(|p| p.substitute(f)).collect(),
+                return_type: Box::new(func.return_type.substitute(f)),
+            }),
+            TypeInfo::Struct(_) | TypeInfo::Unknown => self.clone()
+        }
+    }
+
+    /// Collects the type variables in order of appearance.
+    pub(crate) fn collect_vars(&self, vars: &mut Vec<usize>) {
+        match self {
+            TypeInfo::Var(id) => if !vars.contains(id) { vars.push(*id) },
+            TypeInfo::Tuple(v) => v.iter().for_each(|t| t.collect_vars(vars)),
+            TypeInfo::Function(func) => {
+                func.params.iter().for_each(|p| p.info.collect_vars(vars));
+                func.return_type.info.collect_vars(vars)
+            }
+            TypeInfo::Struct(_) | TypeInfo::Unknown => ()
+        }
+    }
 }
 
 impl Display for TypeInfo {
@@ -24,43 +53,37 @@ impl Display for TypeInfo {
             TypeInfo::Struct(name) => write!(f, "{}", name),
             TypeInfo::Tuple(v) => write!(f, "({})", str_from_iter(v.iter(), ",")),
             TypeInfo::Function(func) => write!(f, "<{}>:{}", str_from_iter(func.params.iter(), ","), func.return_type),
+            TypeInfo::Var(id) => {
+                write!(f, "'{}", (b'a' + (id % 26) as u8) as char)?;
+                if *id >= 26 { write!(f, "{}", id / 26) } else { Ok(()) }
+            }
             TypeInfo::Unknown => write!(f, "#UNKNOWN")
         }
     }
 }
 
-impl From<&FunctionDef> for FuncType {
-    fn from(fd: &FunctionDef) -> Self {
+impl<S: AstState> From<&FunctionDef<S>> for FuncType {
+    fn from(fd: &FunctionDef<S>) -> Self {
         let params = fd.parameters.iter().map(|(_, t)| t.clone()).collect();
         FuncType { params, return_type: Box::new(fd.return_type.clone()) }
     }
 }
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FuncType {
     pub(crate) params: Vec<VariableType>,
     pub(crate) return_type: Box<VariableType>,
 }
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct VariableType {
     pub mutable: bool,
     pub info: TypeInfo,
 }
 
 impl VariableType {
-    pub fn check_expected(self, expected: &VariableType) -> Result<VariableType, String> {
-        if expected.info == TypeInfo::Unknown {
-            if self.check_mutability(expected.mutable) {
-                Ok(VariableType { mutable: expected.mutable, info: self.info })
-            } else { Err("Incorrect mutability.".to_string()) }
-        } else if expected.info != self.info {
-            Err(format!("Wrong type. expected:{}, found:{}", expected.info, self.info))
-        } else if self.check_mutability(expected.mutable) {
-            Ok(VariableType { mutable: expected.mutable, info: self.info })
-        } else {
-            Err("Incorrect mutability.".to_string())
-        }
+    pub(crate) fn substitute(&self, f: &mut impl FnMut(usize) -> TypeInfo) -> VariableType {
+        VariableType { mutable: self.mutable, info: self.info.substitute(f) }
     }
 
     pub fn check_mutability(&self, expected_mutability: bool) -> bool {
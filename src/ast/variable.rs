@@ -1,4 +1,15 @@
+use crate::ast::location::Location;
+use crate::ast::states::AstState;
+use crate::ast::symbol::Symbol;
 use crate::ast::VariableDef;
+use crate::std_library::builtins::Builtin;
+
+/// Separates the module name from the variable name in a qualified name: `A::fib`.
+pub const PATH_SEPARATOR: &str = "::";
+
+pub fn qualified_name(module: &str, name: &str) -> String {
+    format!("{}{}{}", module, PATH_SEPARATOR, name)
+}
 
 #[derive(Debug, Clone)]
 pub enum ConstantValue {
@@ -7,7 +18,35 @@ pub enum ConstantValue {
     String(String),
 }
 
-pub struct VariableName {
-    pub name: String,
-    pub variable: VariableDef,
+pub struct VariableName<S: AstState> {
+    pub name: Symbol,
+    pub location: Location,
+    /// Public variables can be used from other modules and from the host. Others are private to the module.
+    pub public: bool,
+    pub variable: VariableDef<S>,
+}
+
+/// Where a variable reference is defined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Binding {
+    /// Parameter or captured variable of the enclosing function, or `self_fn`. The slot is the index of the variable
+    /// in the frame of the function: the parameters, the captured variables and `self_fn`, in that order.
+    Local { slot: usize },
+    /// Variable of the same module, or a linked variable of another module. The index is the slot of the variable
+    /// in the globals of the module: the variables of the module in their order, then the links.
+    Global { index: usize },
+    /// Function provided by the interpreter, resolved when the module is verified.
+    Builtin(Builtin),
+    /// Function registered by the host with `RunEnv::register_fn`. It is a value like the script functions,
+    /// linked into the globals of the module at the index.
+    Host { index: usize },
+}
+
+/// Variable defined outside of a module, which is linked into its globals when the module is added.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Link {
+    /// Public variable of another module, imported or referred with a qualified name.
+    Module { module: Symbol, name: Symbol },
+    /// Function registered by the host.
+    Host(Symbol),
 }
\ No newline at end of file
@@ -0,0 +1,67 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use crate::parser::error::ParseError;
+use crate::run_env::error::RuntimeError;
+use crate::verify::error::TypeError;
+
+/// Any error returned by the `RunEnv`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Parse(ParseError),
+    Type(TypeError),
+    Runtime(RuntimeError),
+    DuplicateModule { name: String },
+}
+
+impl Error {
+    /// Stable code of the error kind.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Parse(e) => e.code(),
+            Error::Type(e) => e.code(),
+            Error::Runtime(e) => e.code(),
+            Error::DuplicateModule { .. } => "M0001",
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(e) => e.fmt(f),
+            Error::Type(e) => e.fmt(f),
+            Error::Runtime(e) => e.fmt(f),
+            Error::DuplicateModule { name } => write!(f, "[{}] Module {} is already added.", self.code(), name),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Parse(e) => Some(e),
+            Error::Type(e) => Some(e),
+            Error::Runtime(e) => Some(e),
+            Error::DuplicateModule { .. } => None,
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+impl From<TypeError> for Error {
+    fn from(e: TypeError) -> Self {
+        Error::Type(e)
+    }
+}
+
+impl From<RuntimeError> for Error {
+    fn from(e: RuntimeError) -> Self {
+        Error::Runtime(e)
+    }
+}
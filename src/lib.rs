@@ -1,5 +1,8 @@
 pub mod parser;
 pub mod ast;
 pub mod verify;
+pub mod optimize;
 pub mod std_library;
-pub mod run_env;
\ No newline at end of file
+pub mod run_env;
+pub mod vm;
+pub mod error;
\ No newline at end of file
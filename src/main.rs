@@ -17,7 +17,7 @@ fn main() {
         DataObj::Int(v) => println!("type: {}, value: {}",t,v),
         _ => panic!("WRONG TYPE")
     };*/
-    match run_env.find_variable("f").unwrap().borrow().deref(){
+    match run_env.find_variable("Fib::f").unwrap().borrow().deref(){
         DataObj::Int(v) => println!("{}",v),
         _=> panic!("WRONG TYPE")
     }
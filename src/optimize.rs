@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::ast::Module;
+use crate::ast::expr::{Expr, PartialExpr, TupleDef, VariableExpr};
+use crate::ast::states::Verified;
+use crate::ast::symbol::Symbol;
+use crate::ast::types::VariableType;
+use crate::ast::variable::{Binding, ConstantValue};
+use crate::run_env::{calculate, DataObj};
+use crate::std_library::{BoolType, FloatType, IntType, StdMod, StringType};
+
+/// Value of a constant subexpression. Operations are folded with the arithmetic of the std library `S`
+/// the module is run with. Operations which fail, like an integer overflow, are left to run time.
+type Value<S> = DataObj<S>;
+
+/// Module variables whose value is a constant.
+type Constants = HashMap<Symbol, ConstantValue>;
+
+impl Module<Verified> {
+    /// Folds the constant subexpressions, removes the branches of `if`s with constant conditions,
+    /// and inlines the module variables whose value is a constant.
+    /// Evaluation results do not change, but the optimized module uses less fuel.
+    pub fn optimize<S: StdMod>(mut self) -> Module<Verified> {
+        let mut constants = Constants::new();
+        for v in &mut self.variables {
+            v.variable.value.optimize_with::<S>(&constants);
+            if let Some(c) = v.variable.value.as_constant() {
+                constants.insert(v.name.clone(), c.clone());
+            }
+        }
+        self
+    }
+}
+
+impl Expr<Verified> {
+    /// Optimizes an expression run by the host.
+    pub(crate) fn optimize<S: StdMod>(&mut self) {
+        self.optimize_with::<S>(&Constants::new())
+    }
+
+    fn optimize_with<S: StdMod>(&mut self, constants: &Constants) {
+        for e in &mut self.exprs {
+            e.optimize::<S>(constants)
+        }
+        self.remove_dead_branches::<S>();
+        self.fold_constants::<S>();
+    }
+
+    fn as_constant(&self) -> Option<&ConstantValue> {
+        match self.exprs.as_slice() {
+            [PartialExpr::Variable(VariableExpr::Constant(c))] => Some(c),
+            _ => None
+        }
+    }
+
+    /// Value of the expression, if it operates only on constants and none of the operations fail.
+    fn evaluate<S: StdMod>(&self) -> Option<Value<S>> {
+        let mut exprs = self.exprs.iter();
+        let mut value = exprs.next()?.constant::<S>()?;
+        for (e, o) in exprs.zip(&self.operands) {
+            value = calculate(&value, o, &e.constant::<S>()?).ok()?;
+        }
+        Some(value)
+    }
+
+    /// Replaces the constant operands at the start of the expression with their value.
+    /// Only a prefix can be folded, because the operations are evaluated from left to right.
+    fn fold_constants<S: StdMod>(&mut self) {
+        let Some(mut value) = self.exprs.first().and_then(|e| e.constant::<S>()) else { return };
+        let mut prefix = None;
+        for (i, (e, o)) in self.exprs[1..].iter().zip(&self.operands).enumerate() {
+            match e.constant::<S>().and_then(|rhs| calculate(&value, o, &rhs).ok()) {
+                Some(v) => value = v,
+                None => break
+            }
+            // Bools have no literals, so a bool prefix is folded only as the part of a longer one.
+            if let Some(c) = constant_value(&value) {
+                prefix = Some((c, i + 2));
+            }
+        }
+        if let Some((c, len)) = prefix {
+            self.exprs.splice(0..len, [PartialExpr::Variable(VariableExpr::Constant(c))]);
+            self.operands.drain(0..len - 1);
+        }
+    }
+
+    /// Replaces the `if`s with constant conditions with the branch they run.
+    fn remove_dead_branches<S: StdMod>(&mut self) {
+        if let [PartialExpr::If(e)] = self.exprs.as_slice() {
+            if let Some(cond) = e.cond_expr.evaluate::<S>().and_then(|c| as_bool(&c)) {
+                let Some(PartialExpr::If(e)) = self.exprs.pop() else { unreachable!("the expression is an if") };
+                match if cond { Some(e.main_branch) } else { e.else_branch } {
+                    Some(branch) => *self = branch,
+                    None => self.exprs.push(empty(&e.v_type))
+                }
+            }
+            return;
+        }
+        for partial in &mut self.exprs {
+            let PartialExpr::If(e) = partial else { continue };
+            let Some(cond) = e.cond_expr.evaluate::<S>().and_then(|c| as_bool(&c)) else { continue };
+            let replacement = match if cond { Some(&mut e.main_branch) } else { e.else_branch.as_mut() } {
+                None => empty(&e.v_type),
+                // A branch with more parts would need parentheses, which would make it a tuple.
+                Some(branch) if branch.exprs.len() == 1 => branch.exprs.pop().unwrap(),
+                Some(_) => continue
+            };
+            *partial = replacement;
+        }
+    }
+}
+
+impl PartialExpr<Verified> {
+    fn optimize<S: StdMod>(&mut self, constants: &Constants) {
+        match self {
+            PartialExpr::Block(_) => (),
+            PartialExpr::If(e) => {
+                e.cond_expr.optimize_with::<S>(constants);
+                e.main_branch.optimize_with::<S>(constants);
+                if let Some(eb) = &mut e.else_branch {
+                    eb.optimize_with::<S>(constants)
+                }
+            }
+            PartialExpr::FunctionCall(e) => {
+                for p in &mut e.params {
+                    p.optimize_with::<S>(constants)
+                }
+            }
+            PartialExpr::Variable(VariableExpr::Variable { name, binding: Binding::Global { .. }, .. }) => {
+                if let Some(c) = constants.get(name) {
+                    *self = PartialExpr::Variable(VariableExpr::Constant(c.clone()))
+                }
+            }
+            PartialExpr::Variable(_) => (),
+            PartialExpr::Lambda(fd) => Rc::make_mut(&mut fd.expr).optimize_with::<S>(constants),
+            PartialExpr::Tuple(t) => {
+                for e in &mut t.items {
+                    e.optimize_with::<S>(constants)
+                }
+            }
+        }
+    }
+
+    fn constant<S: StdMod>(&self) -> Option<Value<S>> {
+        match self {
+            PartialExpr::Variable(VariableExpr::Constant(c)) => Some(match c {
+                ConstantValue::Integer(v) => S::int_create(*v),
+                ConstantValue::Float(v) => S::float_create(*v),
+                ConstantValue::String(v) => S::string_create(v.clone())
+            }),
+            _ => None
+        }
+    }
+}
+
+fn constant_value<S: StdMod>(value: &Value<S>) -> Option<ConstantValue> {
+    match value {
+        DataObj::Int(v) => Some(ConstantValue::Integer(v.to_i32())),
+        DataObj::Float(v) => Some(ConstantValue::Float(v.to_f32())),
+        DataObj::String(v) => Some(ConstantValue::String(v.as_str().to_string())),
+        _ => None
+    }
+}
+
+fn as_bool<S: StdMod>(value: &Value<S>) -> Option<bool> {
+    match value {
+        DataObj::Bool(b) => Some(b.is_true()),
+        _ => None
+    }
+}
+
+/// Value of an `if` without `else`, whose branch does not run.
+fn empty(v_type: &VariableType) -> PartialExpr<Verified> {
+    PartialExpr::Tuple(TupleDef { items: Vec::new(), v_type: v_type.clone() })
+}
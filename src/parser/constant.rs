@@ -1,12 +1,14 @@
+use std::num::ParseIntError;
 use std::str::FromStr;
 
 use nom::branch::alt;
 use nom::bytes::complete::{is_not, tag};
 use nom::character::complete::{digit1, multispace0};
-use nom::combinator::{map, opt};
+use nom::combinator::{map_res, opt, recognize};
+use nom::error::{Error, ErrorKind};
 use nom::IResult;
 use nom::number::complete::float;
-use nom::sequence::{delimited, preceded};
+use nom::sequence::{delimited, pair, preceded};
 
 use crate::ast::variable::ConstantValue;
 
@@ -20,16 +22,23 @@ fn parse_string_constant(input: &str) -> IResult<&str, ConstantValue> {
     Ok((input, ConstantValue::String(s.to_string())))
 }
 
+/// An integer literal that does not fit in an `Int` fails with `ErrorKind::MapRes`. The failure is not
+/// recoverable, so the parser reports the literal instead of backtracking to another alternative.
 fn parse_number_constant(input: &str) -> IResult<&str, ConstantValue> {
-    let (input, neg) = opt(tag("-"))(input)?;
-    let (input, integer_value) = map(digit1, |v| i32::from_str(v).unwrap())(input)?;
-    let (input, end) = opt(float)(input)?;
-
-    let sign = neg.map_or(1, |_| -1);
+    let number = pair(recognize(pair(opt(tag("-")), digit1)), opt(float));
+    match map_res(number, |(digits, end)| number_value(digits, end))(input) {
+        Err(nom::Err::Error(e @ Error { code: ErrorKind::MapRes, .. })) => Err(nom::Err::Failure(e)),
+        result => result
+    }
+}
 
-    let value = match end {
-        Some(end) => ConstantValue::Float((end + integer_value as f32) * sign as f32),
-        None => ConstantValue::Integer(integer_value * sign)
-    };
-    Ok((input, value))
-}
\ No newline at end of file
+fn number_value(digits: &str, end: Option<f32>) -> Result<ConstantValue, ParseIntError> {
+    match end {
+        Some(end) => {
+            let integer_value = f32::from_str(digits).unwrap_or_default();
+            let sign = if digits.starts_with('-') { -1.0 } else { 1.0 };
+            Ok(ConstantValue::Float((end + integer_value.abs()) * sign))
+        }
+        None => i32::from_str(digits).map(ConstantValue::Integer)
+    }
+}
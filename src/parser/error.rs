@@ -0,0 +1,46 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use crate::ast::location::Location;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The input does not match the grammar. `parser` describes the failing parser.
+    Syntax { parser: String },
+    /// The start of the source was parsed, but `rest` could not be.
+    Unparsed { rest: String },
+    /// The number `literal` does not fit in its type.
+    LiteralOutOfRange { literal: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub location: Location,
+}
+
+impl ParseError {
+    /// Stable code of the error kind.
+    pub fn code(&self) -> &'static str {
+        match self.kind {
+            ParseErrorKind::Syntax { .. } => "P0001",
+            ParseErrorKind::Unparsed { .. } => "P0002",
+            ParseErrorKind::LiteralOutOfRange { .. } => "P0003",
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] ", self.code())?;
+        match &self.kind {
+            ParseErrorKind::Syntax { parser } => write!(f, "Syntax error: {}", parser)?,
+            ParseErrorKind::Unparsed { rest } => write!(f, "Cannot parse: {}", rest)?,
+            ParseErrorKind::LiteralOutOfRange { literal } => write!(f, "Literal out of range: {}", literal)?,
+        };
+        write!(f, " at {}", self.location)
+    }
+}
+
+impl Error for ParseError {}
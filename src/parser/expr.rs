@@ -1,20 +1,27 @@
+use std::rc::Rc;
+
 use nom::{IResult, Parser};
 use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::character::complete::{multispace0, one_of};
+use nom::character::complete::{multispace0, multispace1};
 use nom::combinator::opt;
 use nom::multi::many0;
 use nom::sequence::{delimited, pair, preceded, terminated};
 
-use crate::ast::expr::{Expr, FunctionCallExpr, FunctionDef, IfExpr, Operand, PartialExpr, TupleDef, VariableExpr};
-use crate::parser::variable::{parse_name, parse_variable_type};
+use crate::ast::expr::{Capture, Expr, FunctionCallExpr, FunctionDef, IfExpr, Operand, PartialExpr, TupleDef, VariableExpr};
+use crate::ast::location::Location;
+use crate::ast::states::Unverified;
+use crate::ast::symbol::SymbolTable;
+use crate::ast::types::{TypeInfo, VariableType};
+use crate::parser::variable::{parse_name, parse_path, parse_variable_type};
 use crate::parser::constant::parse_constant_value;
 use crate::parser::utils::separated_list0_with_spaces;
 
-pub(crate) fn parse_expr(input: &str) -> IResult<&str, Expr> {
+pub(crate) fn parse_expr<'a>(input: &'a str, symbols: &SymbolTable) -> IResult<&'a str, Expr<Unverified>> {
     let (input, _) = multispace0(input)?;
-    let (input, first) = parse_partial_expr(input)?;
-    let (input, rest) = many0(pair(parse_operand, parse_partial_expr))(input)?;
+    let location = Location::pending(input);
+    let (input, first) = parse_partial_expr(input, symbols)?;
+    let (input, rest) = many0(pair(parse_operand, |i| parse_partial_expr(i, symbols)))(input)?;
     let (input, _) = opt(pair(multispace0, tag(";")))(input)?;
 
     let mut exprs = Vec::new();
@@ -24,59 +31,76 @@ pub(crate) fn parse_expr(input: &str) -> IResult<&str, Expr> {
         exprs.push(e);
         operands.push(op);
     };
-    Ok((input, Expr { exprs, operands }))
+    Ok((input, Expr { exprs, operands, location, v_type: () }))
 }
 
-fn parse_partial_expr(input: &str) -> IResult<&str, PartialExpr> {
-    alt((parse_if, parse_func_call, parse_lambda, parse_tuple, parse_variable))(input)
+fn parse_partial_expr<'a>(input: &'a str, symbols: &SymbolTable) -> IResult<&'a str, PartialExpr<Unverified>> {
+    alt((
+        |i| parse_if(i, symbols),
+        |i| parse_func_call(i, symbols),
+        |i| parse_lambda(i, symbols),
+        |i| parse_tuple(i, symbols),
+        |i| parse_variable(i, symbols),
+    ))(input)
 }
 
-fn parse_variable(input: &str) -> IResult<&str, PartialExpr> {
-    let (input, v) = preceded(multispace0, alt((parse_name.map(|name| VariableExpr::Variable(name.to_string())), parse_constant_value.map(|c| VariableExpr::Constant(c)))))(input)?;
+fn parse_variable<'a>(input: &'a str, symbols: &SymbolTable) -> IResult<&'a str, PartialExpr<Unverified>> {
+    let (input, _) = multispace0(input)?;
+    let location = Location::pending(input);
+    let (input, v) = alt(((|i| parse_path(i, symbols)).map(|name| VariableExpr::Variable { name, location, binding: (), v_type: () }), parse_constant_value.map(VariableExpr::Constant)))(input)?;
     Ok((input, PartialExpr::Variable(v)))
 }
 
-fn parse_lambda(input: &str) -> IResult<&str, PartialExpr> {
+fn parse_lambda<'a>(input: &'a str, symbols: &SymbolTable) -> IResult<&'a str, PartialExpr<Unverified>> {
     let (input, _) = multispace0(input)?;
-    let (input, parameters) = delimited(tag("<"), separated_list0_with_spaces(tag(","), pair(parse_name, preceded(tag(":"), parse_variable_type))), tag(">"))(input)?;
+    let location = Location::pending(input);
+    let (input, pure) = opt(pair(tag("pure"), multispace1))(input)?;
+    let (input, memo) = opt(pair(tag("memo"), multispace1))(input)?;
+    let (input, parameters) = delimited(tag("<"), separated_list0_with_spaces(tag(","), pair(|i| parse_name(i, symbols), opt(preceded(tag(":"), parse_variable_type)))), tag(">"))(input)?;
     let (input, _) = multispace0(input)?;
-    let (input, closure) = opt(delimited(tag("<"), separated_list0_with_spaces(tag(","), pair(opt(tag("mut ")), parse_name)), tag(">")))(input)?;
-    let (input, return_type) = preceded(pair(multispace0, tag(":")), parse_variable_type)(input)?;
+    let (input, closure) = opt(delimited(tag("<"), separated_list0_with_spaces(tag(","), pair(opt(tag("mut ")), |i| parse_name(i, symbols))), tag(">")))(input)?;
+    let (input, return_type) = opt(preceded(pair(multispace0, tag(":")), parse_variable_type))(input)?;
     let (input, _) = pair(multispace0, tag("->"))(input)?;
-    let (input, expr) = parse_expr(input)?;
+    let (input, expr) = parse_expr(input, symbols)?;
 
-    let closure = closure.map(|c| c.iter().map(|(m, name)| (name.to_owned(), m.is_some())).collect()).unwrap_or(Vec::new());
-    Ok((input, PartialExpr::Lambda(FunctionDef { parameters, closure, return_type, expr })))
+    let closure = closure.map(|c| c.into_iter().map(|(m, name)| Capture { name, mutable: m.is_some(), binding: () }).collect()).unwrap_or_default();
+    let unknown = || VariableType { mutable: false, info: TypeInfo::Unknown };
+    let parameters = parameters.into_iter().map(|(name, t)| (name, t.unwrap_or_else(unknown))).collect();
+    let return_type = return_type.unwrap_or_else(unknown);
+    Ok((input, PartialExpr::Lambda(FunctionDef { parameters, closure, return_type, expr: Rc::new(expr), location, memo: memo.is_some(), pure: pure.is_some(), effects: (), uses_self: false })))
 }
 
-fn parse_if(input: &str) -> IResult<&str, PartialExpr> {
+fn parse_if<'a>(input: &'a str, symbols: &SymbolTable) -> IResult<&'a str, PartialExpr<Unverified>> {
     let (input, _) = pair(multispace0, tag("if"))(input)?;
-    let (input, cond_expr) = parse_expr(input)?;
-    let (input, main_branch) = parse_expr(input)?;
-    let (input, else_branch) = opt(preceded(pair(multispace0, tag("else")), parse_expr))(input)?;
-    Ok((input, PartialExpr::If(IfExpr { cond_expr, main_branch, else_branch })))
+    let (input, cond_expr) = parse_expr(input, symbols)?;
+    let (input, main_branch) = parse_expr(input, symbols)?;
+    let (input, else_branch) = opt(preceded(pair(multispace0, tag("else")), |i| parse_expr(i, symbols)))(input)?;
+    Ok((input, PartialExpr::If(IfExpr { cond_expr, main_branch, else_branch, v_type: () })))
 }
 
-fn parse_func_call(input: &str) -> IResult<&str, PartialExpr> {
+fn parse_func_call<'a>(input: &'a str, symbols: &SymbolTable) -> IResult<&'a str, PartialExpr<Unverified>> {
     let (input, _) = multispace0(input)?;
-    let (input, name) = terminated(parse_name, tag("("))(input)?;
-    let (input, params) = separated_list0_with_spaces(tag(","), parse_expr)(input)?;
+    let location = Location::pending(input);
+    let (input, name) = terminated(|i| parse_path(i, symbols), tag("("))(input)?;
+    let (input, params) = separated_list0_with_spaces(tag(","), |i| parse_expr(i, symbols))(input)?;
     let (input, _) = tag(")")(input)?;
-    Ok((input, PartialExpr::FunctionCall(FunctionCallExpr { name, params })))
+    Ok((input, PartialExpr::FunctionCall(FunctionCallExpr { name, params, location, binding: (), v_type: (), tail: false })))
 }
 
-pub(crate) fn parse_tuple(input: &str) -> IResult<&str, PartialExpr> {
+pub(crate) fn parse_tuple<'a>(input: &'a str, symbols: &SymbolTable) -> IResult<&'a str, PartialExpr<Unverified>> {
     let (input, _) = multispace0(input)?;
-    let (input, items) = delimited(tag("("), separated_list0_with_spaces(tag(","), parse_expr), tag(")"))(input)?;
-    Ok((input, PartialExpr::Tuple(TupleDef { items })))
+    let (input, items) = delimited(tag("("), separated_list0_with_spaces(tag(","), |i| parse_expr(i, symbols)), tag(")"))(input)?;
+    Ok((input, PartialExpr::Tuple(TupleDef { items, v_type: () })))
 }
 
 fn parse_operand(input: &str) -> IResult<&str, Operand> {
-    let (input, op) = preceded(multispace0, one_of("+-<"))(input)?;
+    let (input, op) = preceded(multispace0, alt((tag("=="), tag("!="), tag("+"), tag("-"), tag("<"))))(input)?;
     let op = match op {
-        '+' => Operand::Plus,
-        '-' => Operand::Minus,
-        '<' => Operand::Lt,
+        "+" => Operand::Plus,
+        "-" => Operand::Minus,
+        "<" => Operand::Lt,
+        "==" => Operand::Eq,
+        "!=" => Operand::Ne,
         _ => unreachable!()
     };
     Ok((input, op))
@@ -1,27 +1,124 @@
 use std::collections::HashSet;
 
-use nom::{IResult};
-use nom::character::complete::{ multispace0};
+use nom::{Finish, IResult, Parser};
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::character::complete::{multispace0, multispace1};
+use nom::error::ErrorKind;
 use nom::multi::many0;
+use nom::sequence::{delimited, pair, preceded};
 
-use crate::ast::{Module};
+use crate::ast::{Import, Module};
+use crate::ast::expr::Expr;
+use crate::ast::location::{LineIndex, Location};
+use crate::ast::types::{FuncType, TypeInfo};
+use crate::ast::variable::VariableName;
 use crate::ast::states::Unverified;
-use crate::parser::variable::parse_variable_def;
+use crate::ast::symbol::SymbolTable;
+use crate::parser::error::{ParseError, ParseErrorKind};
+use crate::parser::expr::parse_expr;
+use crate::parser::utils::separated_list0_with_spaces;
+use crate::parser::variable::{parse_func_type, parse_name, parse_variable_def};
 
 mod constant;
+pub mod error;
 pub(crate) mod expr;
 mod utils;
 pub(crate) mod variable;
 
-pub fn parse_module(input: &str) -> IResult<&str, Module<Unverified>> {
-    /*    enum ModuleItem{
-            Var(VariableName),
-            Str(StructDef)
-        }*/
-    let (input, variables) = many0(parse_variable_def)(input)?;
+/// Parses a complete module. The module has its own symbol table.
+pub fn parse(code: &str) -> Result<Module<Unverified>, ParseError> {
+    parse_with(code, &SymbolTable::default())
+}
+
+/// Parses a complete module, interning its identifiers into `symbols`.
+pub fn parse_with(code: &str, symbols: &SymbolTable) -> Result<Module<Unverified>, ParseError> {
+    let (unparsed, module) = parse_module(code, symbols).finish().map_err(|e| syntax_error(code, e))?;
+    check_unparsed(code, unparsed)?;
+    Ok(module)
+}
+
+/// Parses a complete expression, interning its identifiers into `symbols`.
+pub fn parse_expression(code: &str, symbols: &SymbolTable) -> Result<Expr<Unverified>, ParseError> {
+    let (unparsed, mut expr) = parse_expr(code, symbols).finish().map_err(|e| syntax_error(code, e))?;
+    check_unparsed(code, unparsed)?;
+    let index = LineIndex::new(code);
+    expr.locations_mut(&mut |l| index.resolve(l));
+    Ok(expr)
+}
+
+/// Parses the type of a function: `<String, Int>:()`.
+pub(crate) fn parse_signature(code: &str) -> Result<FuncType, ParseError> {
+    let (unparsed, info) = delimited(multispace0, parse_func_type, multispace0)(code).finish().map_err(|e| syntax_error(code, e))?;
+    check_unparsed(code, unparsed)?;
+    match info {
+        TypeInfo::Function(func_type) => Ok(func_type),
+        _ => unreachable!("parse_func_type returns a function type")
+    }
+}
+
+fn syntax_error(code: &str, e: nom::error::Error<&str>) -> ParseError {
+    let mut location = Location::pending(e.input);
+    LineIndex::new(code).resolve(&mut location);
+    let kind = match e.code {
+        ErrorKind::MapRes => {
+            let literal = e.input.split(|c: char| c != '-' && !c.is_ascii_digit()).next().unwrap_or_default();
+            ParseErrorKind::LiteralOutOfRange { literal: literal.to_string() }
+        }
+        code => ParseErrorKind::Syntax { parser: code.description().to_string() }
+    };
+    ParseError { kind, location }
+}
+
+fn check_unparsed(code: &str, unparsed: &str) -> Result<(), ParseError> {
+    if unparsed.is_empty() {
+        Ok(())
+    } else {
+        let mut location = Location::pending(unparsed);
+        LineIndex::new(code).resolve(&mut location);
+        let rest = unparsed.lines().next().unwrap_or_default().to_string();
+        Err(ParseError { kind: ParseErrorKind::Unparsed { rest }, location })
+    }
+}
+
+/// Parses the items of a module. The locations in the module are relative to `input`.
+pub fn parse_module<'a>(input: &'a str, symbols: &SymbolTable) -> IResult<&'a str, Module<Unverified>> {
+    enum ModuleItem {
+        Var(VariableName<Unverified>),
+        Import(Import),
+        //Str(StructDef)
+    }
+    let original = input;
+    let (input, items) = many0(alt((
+        (|i| parse_variable_def(i, symbols)).map(ModuleItem::Var),
+        (|i| parse_import(i, symbols)).map(ModuleItem::Import),
+    )))(input)?;
     let (input, _) = multispace0(input)?;
 
-    Ok((input, Module { variables, structs: HashSet::new(), _state: Default::default() }))
+    let mut imports = Vec::new();
+    let mut variables = Vec::new();
+    for item in items {
+        match item {
+            ModuleItem::Var(v) => variables.push(v),
+            ModuleItem::Import(i) => imports.push(i)
+        }
+    }
+    let mut module = Module { imports, variables, structs: HashSet::new(), links: Vec::new(), symbols: symbols.clone(), _state: Default::default() };
+    let index = LineIndex::new(original);
+    module.locations_mut(&mut |l| index.resolve(l));
+    Ok((input, module))
+}
+
+fn parse_import<'a>(input: &'a str, symbols: &SymbolTable) -> IResult<&'a str, Import> {
+    let (input, _) = multispace0(input)?;
+    let location = Location::pending(input);
+    let (input, _) = pair(tag("import"), multispace1)(input)?;
+    let (input, module) = parse_name(input, symbols)?;
+    let (input, names) = preceded(tag("."), alt((
+        delimited(tag("{"), separated_list0_with_spaces(tag(","), |i| parse_name(i, symbols)), tag("}")),
+        (|i| parse_name(i, symbols)).map(|name| vec![name])
+    )))(input)?;
+    Ok((input, Import { module, names, location }))
 }
 
 /*pub fn parse_struct(input: &str) -> IResult<&str, String>{
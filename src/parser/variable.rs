@@ -6,21 +6,27 @@ use nom::combinator::{opt, peek};
 use nom::sequence::{delimited, pair, preceded, tuple};
 
 use crate::ast::types::{FuncType, TypeInfo, VariableType};
-use crate::ast::variable::VariableName;
+use crate::ast::variable::{qualified_name, VariableName, PATH_SEPARATOR};
+use crate::ast::location::Location;
+use crate::ast::states::Unverified;
+use crate::ast::symbol::{Symbol, SymbolTable};
 use crate::ast::VariableDef;
 use crate::parser::expr::parse_expr;
 use crate::parser::utils::separated_list0_with_spaces;
 
-pub(crate) fn parse_variable_def(input: &str) -> IResult<&str, VariableName> {
-    let (input, _) = tuple((multispace0, tag("let"), multispace1))(input)?;
+pub(crate) fn parse_variable_def<'a>(input: &'a str, symbols: &SymbolTable) -> IResult<&'a str, VariableName<Unverified>> {
+    let (input, _) = multispace0(input)?;
+    let location = Location::pending(input);
+    let (input, public) = opt(pair(tag("pub"), multispace1))(input)?;
+    let (input, _) = pair(tag("let"), multispace1)(input)?;
     let (input, mutable) = opt(tag("mut "))(input)?;
-    let (input, name) = parse_name(input)?;
+    let (input, name) = parse_name(input, symbols)?;
     let (input, type_info) = opt(preceded(pair(multispace0, tag(":")), parse_type_info))(input)?;
     let (input, _) = tuple((multispace0, tag("=")))(input)?;
-    let (input, expr) = parse_expr(input)?;
-    let variable = VariableDef { value: expr, v_type: VariableType { mutable: mutable.is_some(), info: type_info.unwrap_or(TypeInfo::Unknown) } };
+    let (input, expr) = parse_expr(input, symbols)?;
+    let variable = VariableDef { value: expr, v_type: VariableType { mutable: mutable.is_some(), info: type_info.unwrap_or(TypeInfo::Unknown) }, comparable: Vec::new() };
 
-    Ok((input, VariableName { name, variable }))
+    Ok((input, VariableName { name, location, public: public.is_some(), variable }))
 }
 
 pub(crate) fn parse_variable_type(input: &str) -> IResult<&str, VariableType> {
@@ -31,7 +37,7 @@ pub(crate) fn parse_variable_type(input: &str) -> IResult<&str, VariableType> {
 
 pub(crate) fn parse_type_info(input: &str) -> IResult<&str, TypeInfo> {
     let (input, _) = multispace0(input)?;
-    alt((parse_name.map(|name| TypeInfo::Struct(name)), parse_func_type, parse_tuple_type))(input)
+    alt((parse_word.map(|name| TypeInfo::Struct(name.to_string())), parse_func_type, parse_tuple_type))(input)
 }
 
 pub(crate) fn parse_func_type(input: &str) -> IResult<&str, TypeInfo> {
@@ -47,10 +53,22 @@ pub(crate) fn parse_tuple_type(input: &str) -> IResult<&str, TypeInfo> {
 }
 
 
-pub(crate) fn parse_name(input: &str) -> IResult<&str, String> {
+fn parse_word(input: &str) -> IResult<&str, &str> {
     let (input, _) = multispace0(input)?;
     let (input, _) = peek(alt((alpha1, tag("_"))))(input)?;
-    let (input, name) = take_while1(|c: char| c.is_alphanumeric() || c == '_')(input)?;
+    take_while1(|c: char| c.is_alphanumeric() || c == '_')(input)
+}
 
-    Ok((input, name.to_string()))
-}
\ No newline at end of file
+pub(crate) fn parse_name<'a>(input: &'a str, symbols: &SymbolTable) -> IResult<&'a str, Symbol> {
+    parse_word.map(|name| symbols.intern(name)).parse(input)
+}
+
+/// Parses a variable name, which may be qualified with a module name: `A::fib`.
+pub(crate) fn parse_path<'a>(input: &'a str, symbols: &SymbolTable) -> IResult<&'a str, Symbol> {
+    let (input, first) = parse_word(input)?;
+    let (input, second) = opt(preceded(tag(PATH_SEPARATOR), parse_word))(input)?;
+    Ok((input, match second {
+        Some(name) => symbols.intern(&qualified_name(first, name)),
+        None => symbols.intern(first)
+    }))
+}
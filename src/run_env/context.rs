@@ -0,0 +1,174 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::run_env::data_obj::{DataCell, DataObj, RefDataObj};
+use crate::run_env::error::{RuntimeError, RuntimeErrorKind};
+use crate::run_env::memo::Memo;
+use crate::run_env::memory::Memory;
+use crate::std_library::StdMod;
+
+/// Default maximum depth of script function calls. A script call of a simple function uses about 13 KB
+/// of the native stack in debug builds and 5 KB in release builds.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 256;
+
+/// Default maximum depth of the expressions evaluated by the tree-walker, counted over all the running calls.
+/// A nested expression uses up to about 2 KB of the native stack in debug builds and 0.7 KB in release builds,
+/// so together with the calls this leaves room for the host on an 8 MB main thread.
+pub const DEFAULT_MAX_NESTING_DEPTH: usize = 2048;
+
+/// The deadline is checked once in this many evaluation steps.
+const DEADLINE_CHECK_INTERVAL: u64 = 1024;
+
+/// Stops the running execution of a `RunEnv` from another thread.
+#[derive(Clone, Default)]
+pub struct CancelHandle {
+    state: Arc<CancelState>,
+}
+
+#[derive(Default)]
+struct CancelState {
+    /// Number of the finished executions, which is also the number of the running or the next execution.
+    finished: AtomicU64,
+    /// Number of the cancelled execution plus one, or 0 if nothing was cancelled.
+    cancelled: AtomicU64,
+}
+
+impl CancelHandle {
+    /// Requests the running execution, or the next one if none is running, to stop with a runtime error.
+    /// A request, which is not observed before its execution ends, does not stop the later executions.
+    pub fn cancel(&self) {
+        let execution = self.state.finished.load(Ordering::SeqCst);
+        self.state.cancelled.fetch_max(execution + 1, Ordering::SeqCst);
+    }
+
+    /// Returns if the running or the next execution is requested to stop.
+    pub fn is_cancelled(&self) -> bool {
+        self.is_cancelled_execution(self.state.finished.load(Ordering::SeqCst))
+    }
+
+    fn is_cancelled_execution(&self, execution: u64) -> bool {
+        self.state.cancelled.load(Ordering::SeqCst) == execution + 1
+    }
+
+    /// Number of the execution starting now.
+    fn start(&self) -> u64 {
+        self.state.finished.load(Ordering::SeqCst)
+    }
+
+    /// Ends the running execution, so that the requests made for it are discarded.
+    pub(crate) fn finish(&self) {
+        self.state.finished.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// State of one execution, shared by every function called during it.
+pub struct RunContext {
+    max_call_depth: usize,
+    call_depth: Cell<usize>,
+    max_nesting_depth: usize,
+    nesting_depth: Cell<usize>,
+    /// Maximum number of evaluation steps, unlimited if `None`.
+    fuel: Option<u64>,
+    fuel_used: Cell<u64>,
+    memory: Rc<Memory>,
+    cancel: CancelHandle,
+    /// Number of the execution in the executions of `cancel`.
+    execution: u64,
+    /// The execution must end before `Instant`. The `Duration` is the timeout it was computed from.
+    deadline: Option<(Instant, Duration)>,
+    /// Maximum number of results cached by each memoized function value created during the execution.
+    memo_capacity: usize,
+}
+
+impl RunContext {
+    pub fn new(max_call_depth: usize, max_nesting_depth: usize, fuel: Option<u64>, memory: Rc<Memory>, cancel: CancelHandle, timeout: Option<Duration>, memo_capacity: usize) -> Self {
+        RunContext {
+            max_call_depth,
+            call_depth: Cell::new(0),
+            max_nesting_depth,
+            nesting_depth: Cell::new(0),
+            fuel,
+            fuel_used: Cell::new(0),
+            memory,
+            execution: cancel.start(),
+            cancel,
+            deadline: timeout.map(|t| (Instant::now() + t, t)),
+            memo_capacity,
+        }
+    }
+
+    /// Creates the cache of a memoized function value.
+    pub(crate) fn memo<S: StdMod>(&self) -> Rc<Memo<S>> {
+        Rc::new(Memo::new(self.memo_capacity))
+    }
+
+    /// Creates a value. Values on the heap are counted in the memory of the execution, immediates are not.
+    pub fn alloc<S: StdMod>(&self, value: DataObj<S>) -> Result<RefDataObj<S>, RuntimeError> {
+        RefDataObj::immediate(value).or_else(|value| {
+            let allocation = self.memory.allocate(value.approximate_size())?;
+            Ok(RefDataObj::Heap(Rc::new(DataCell::new(value, Some(allocation)))))
+        })
+    }
+
+    /// Starts an evaluation step: an expression or a function call. The step consumes one unit of fuel.
+    /// Fails if the execution is out of fuel, cancelled or past its deadline.
+    pub fn step(&self) -> Result<(), RuntimeError> {
+        let used = self.fuel_used.get() + 1;
+        if let Some(fuel) = self.fuel {
+            if used > fuel {
+                return Err(RuntimeErrorKind::OutOfFuel { fuel }.into());
+            }
+        }
+        self.fuel_used.set(used);
+        if self.cancel.is_cancelled_execution(self.execution) {
+            return Err(RuntimeErrorKind::Cancelled.into());
+        }
+        match self.deadline {
+            Some((deadline, timeout)) if used.is_multiple_of(DEADLINE_CHECK_INTERVAL) && Instant::now() > deadline => {
+                Err(RuntimeErrorKind::Timeout { timeout }.into())
+            }
+            _ => Ok(())
+        }
+    }
+
+    pub fn fuel_used(&self) -> u64 {
+        self.fuel_used.get()
+    }
+
+    /// Starts a function call. The call ends when the returned guard is dropped.
+    pub(crate) fn enter_call(&self) -> Result<DepthGuard<'_>, RuntimeError> {
+        DepthGuard::enter(&self.call_depth, self.max_call_depth)
+            .ok_or_else(|| RuntimeErrorKind::MaxCallDepth { depth: self.max_call_depth }.into())
+    }
+
+    /// Starts the evaluation of an expression on the native stack. It ends when the returned guard is dropped.
+    pub(crate) fn enter_expr(&self) -> Result<DepthGuard<'_>, RuntimeError> {
+        DepthGuard::enter(&self.nesting_depth, self.max_nesting_depth)
+            .ok_or_else(|| RuntimeErrorKind::MaxNestingDepth { depth: self.max_nesting_depth }.into())
+    }
+}
+
+/// Keeps a depth counter increased while it is alive.
+pub(crate) struct DepthGuard<'a> {
+    depth: &'a Cell<usize>,
+}
+
+impl<'a> DepthGuard<'a> {
+    fn enter(depth: &'a Cell<usize>, max: usize) -> Option<Self> {
+        let d = depth.get();
+        if d >= max {
+            return None;
+        }
+        depth.set(d + 1);
+        Some(DepthGuard { depth })
+    }
+}
+
+impl Drop for DepthGuard<'_> {
+    fn drop(&mut self) {
+        self.depth.set(self.depth.get() - 1)
+    }
+}
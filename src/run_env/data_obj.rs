@@ -1,8 +1,83 @@
-use std::cell::RefCell;
+use std::cell::{Ref, RefCell};
+use std::mem::size_of;
+use std::ops::Deref;
 use std::rc::Rc;
-use crate::std_library::StdMod;
 
-pub type RefDataObj<S> = Rc<RefCell<DataObj<S>>>;
+use crate::ast::types::TypeInfo;
+use crate::run_env::memory::Allocation;
+use crate::std_library::{BoolType, FloatType, IntType, StdMod, StringType};
+use crate::verify::{BOOL_TYPE, FLOAT_TYPE, INT_TYPE, STRING_TYPE};
+
+/// Value of a variable or of an evaluated expression. Ints, floats, bools and empty values are stored inline,
+/// so the arithmetic does not allocate. Strings, functions and tuples are shared on the heap.
+#[derive(Clone)]
+pub enum RefDataObj<S: StdMod> {
+    Int(S::INT),
+    Float(S::FLOAT),
+    Bool(S::BOOL),
+    Empty(S::EMPTY),
+    Heap(Rc<DataCell<S>>),
+}
+
+impl<S: StdMod> RefDataObj<S> {
+    /// Stores the value inline. Values which must be allocated on the heap are returned back.
+    pub(crate) fn immediate(value: DataObj<S>) -> Result<Self, DataObj<S>> {
+        match value {
+            DataObj::Int(v) => Ok(RefDataObj::Int(v)),
+            DataObj::Float(v) => Ok(RefDataObj::Float(v)),
+            DataObj::Bool(v) => Ok(RefDataObj::Bool(v)),
+            DataObj::Empty(v) => Ok(RefDataObj::Empty(v)),
+            v => Err(v)
+        }
+    }
+
+    pub fn borrow(&self) -> DataRef<'_, S> {
+        match self {
+            RefDataObj::Int(v) => DataRef::Immediate(DataObj::Int(v.clone())),
+            RefDataObj::Float(v) => DataRef::Immediate(DataObj::Float(v.clone())),
+            RefDataObj::Bool(v) => DataRef::Immediate(DataObj::Bool(v.clone())),
+            RefDataObj::Empty(v) => DataRef::Immediate(DataObj::Empty(v.clone())),
+            RefDataObj::Heap(cell) => DataRef::Heap(cell.borrow())
+        }
+    }
+}
+
+/// Borrowed value of a `RefDataObj`.
+pub enum DataRef<'a, S: StdMod> {
+    Immediate(DataObj<S>),
+    Heap(Ref<'a, DataObj<S>>),
+}
+
+impl<S: StdMod> Deref for DataRef<'_, S> {
+    type Target = DataObj<S>;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            DataRef::Immediate(v) => v,
+            DataRef::Heap(v) => v
+        }
+    }
+}
+
+/// Shared value on the heap. A value created by a script is counted in the memory of its `RunEnv`, while it is alive.
+pub struct DataCell<S: StdMod> {
+    value: RefCell<DataObj<S>>,
+    _allocation: Option<Allocation>,
+}
+
+impl<S: StdMod> DataCell<S> {
+    pub(crate) fn new(value: DataObj<S>, allocation: Option<Allocation>) -> Self {
+        DataCell { value: RefCell::new(value), _allocation: allocation }
+    }
+}
+
+impl<S: StdMod> Deref for DataCell<S> {
+    type Target = RefCell<DataObj<S>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
 
 #[derive(Clone)]
 pub enum DataObj<S: StdMod> {
@@ -12,11 +87,22 @@ pub enum DataObj<S: StdMod> {
     Func(S::FUNC),
     Bool(S::BOOL),
     Empty(S::EMPTY),
+    Tuple(Vec<DataObj<S>>),
 }
 
 impl<S: StdMod> DataObj<S> {
+    /// Creates a value, which is not counted in the memory of any `RunEnv`.
     pub fn into_ref(self) -> RefDataObj<S> {
-        Rc::new(RefCell::new(self))
+        RefDataObj::immediate(self).unwrap_or_else(|v| RefDataObj::Heap(Rc::new(DataCell::new(v, None))))
+    }
+
+    /// Approximate size of the value on the heap in bytes. Strings are counted by their characters.
+    pub fn approximate_size(&self) -> usize {
+        match self {
+            DataObj::String(s) => size_of::<Self>() + s.len() as usize,
+            DataObj::Tuple(items) => size_of::<Self>() + items.iter().map(|i| i.approximate_size()).sum::<usize>(),
+            _ => size_of::<Self>()
+        }
     }
 
     pub fn type_str(&self) -> &str {
@@ -26,7 +112,70 @@ impl<S: StdMod> DataObj<S> {
             DataObj::String(_) => "STRING",
             DataObj::Func(_) => "FUNC",
             DataObj::Bool(_) => "BOOL",
-            DataObj::Empty(_) => "EMPTY"
+            DataObj::Empty(_) => "EMPTY",
+            DataObj::Tuple(_) => "TUPLE",
+        }
+    }
+
+    /// Tells if the value can have the type `info`. Type variables match any value, and the types of the parameters
+    /// and the result of a function are not checked.
+    pub(crate) fn has_type(&self, info: &TypeInfo) -> bool {
+        match (self, info) {
+            (_, TypeInfo::Var(_) | TypeInfo::Unknown) => true,
+            (DataObj::Int(_), TypeInfo::Struct(name)) => name == INT_TYPE,
+            (DataObj::Float(_), TypeInfo::Struct(name)) => name == FLOAT_TYPE,
+            (DataObj::String(_), TypeInfo::Struct(name)) => name == STRING_TYPE,
+            (DataObj::Bool(_), TypeInfo::Struct(name)) => name == BOOL_TYPE,
+            (DataObj::Func(_), TypeInfo::Function(_)) => true,
+            (DataObj::Empty(_), TypeInfo::Tuple(items)) => items.is_empty(),
+            (DataObj::Tuple(values), TypeInfo::Tuple(items)) => {
+                values.len() == items.len() && values.iter().zip(items).all(|(v, t)| v.has_type(t))
+            }
+            _ => false
+        }
+    }
+
+    /// Structural equality. `None` if the values can not be compared: functions or different types.
+    pub fn equals(&self, other: &Self) -> Option<bool> {
+        match (self, other) {
+            (DataObj::Int(l), DataObj::Int(r)) => Some(l.eq(r).is_true()),
+            (DataObj::Float(l), DataObj::Float(r)) => Some(l.eq(r).is_true()),
+            (DataObj::String(l), DataObj::String(r)) => Some(l.eq(r).is_true()),
+            (DataObj::Bool(l), DataObj::Bool(r)) => Some(l.is_true() == r.is_true()),
+            (DataObj::Empty(_), DataObj::Empty(_)) => Some(true),
+            (DataObj::Tuple(l), DataObj::Tuple(r)) if l.len() == r.len() => {
+                for (l, r) in l.iter().zip(r) {
+                    if !l.equals(r)? {
+                        return Some(false);
+                    }
+                }
+                Some(true)
+            }
+            _ => None
+        }
+    }
+
+    /// Ordering of the values: `false` is less than `true`, tuples are ordered lexicographically.
+    /// `None` if the values can not be ordered.
+    pub fn less_than(&self, other: &Self) -> Option<bool> {
+        match (self, other) {
+            (DataObj::Int(l), DataObj::Int(r)) => Some(l.lt(r).is_true()),
+            (DataObj::Float(l), DataObj::Float(r)) => Some(l.lt(r).is_true()),
+            (DataObj::String(l), DataObj::String(r)) => Some(l.lt(r).is_true()),
+            (DataObj::Bool(l), DataObj::Bool(r)) => Some(!l.is_true() && r.is_true()),
+            (DataObj::Empty(_), DataObj::Empty(_)) => Some(false),
+            (DataObj::Tuple(l), DataObj::Tuple(r)) if l.len() == r.len() => {
+                for (l, r) in l.iter().zip(r) {
+                    if l.less_than(r)? {
+                        return Some(true);
+                    }
+                    if r.less_than(l)? {
+                        return Some(false);
+                    }
+                }
+                Some(false)
+            }
+            _ => None
         }
     }
 }
\ No newline at end of file
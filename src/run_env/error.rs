@@ -0,0 +1,127 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+use crate::ast::expr::Operand;
+use crate::ast::location::Location;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuntimeErrorKind {
+    VariableNotFound { name: String },
+    /// The operation is not defined for the runtime types of the operands.
+    InvalidOperands { lhs: String, operand: Operand, rhs: String },
+    NotFunction { name: String },
+    /// A builtin function was called with arguments of wrong runtime types.
+    InvalidArguments { function: String, found: Vec<String> },
+    /// The result of an integer operation does not fit in the integer type of the `StdMod`.
+    IntegerOverflow { operand: Operand },
+    /// The range from `start` to `end` is not inside a string of length `len`.
+    InvalidRange { start: i32, end: i32, len: i32 },
+    /// Script function calls are nested deeper than allowed by the `RunEnv`.
+    MaxCallDepth { depth: usize },
+    /// Expressions are nested deeper than allowed by the `RunEnv`, counting the expressions of all the running calls.
+    MaxNestingDepth { depth: usize },
+    /// The execution used all of its fuel.
+    OutOfFuel { fuel: u64 },
+    /// The live values of the `RunEnv` would use more memory than allowed.
+    OutOfMemory { limit: usize },
+    /// The execution was stopped with a `CancelHandle`.
+    Cancelled,
+    /// The execution did not end within the timeout of the `RunEnv`.
+    Timeout { timeout: Duration },
+    ConditionNotBool { found: String },
+    /// A function registered by the host returned an error.
+    HostFunction { function: String, message: String },
+    Unimplemented { feature: String },
+}
+
+/// Call of a script function, which was running when the error occurred.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackFrame {
+    pub function: String,
+    /// Location of the call.
+    pub location: Location,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeError {
+    pub kind: RuntimeErrorKind,
+    /// Location of the innermost expression containing the error, if known.
+    pub location: Option<Location>,
+    /// Script call stack, the innermost call first.
+    pub stack: Vec<StackFrame>,
+}
+
+impl RuntimeError {
+    /// Stable code of the error kind.
+    pub fn code(&self) -> &'static str {
+        match self.kind {
+            RuntimeErrorKind::VariableNotFound { .. } => "R0001",
+            RuntimeErrorKind::InvalidOperands { .. } => "R0002",
+            RuntimeErrorKind::NotFunction { .. } => "R0003",
+            RuntimeErrorKind::ConditionNotBool { .. } => "R0004",
+            RuntimeErrorKind::Unimplemented { .. } => "R0005",
+            RuntimeErrorKind::InvalidArguments { .. } => "R0006",
+            RuntimeErrorKind::IntegerOverflow { .. } => "R0007",
+            RuntimeErrorKind::InvalidRange { .. } => "R0008",
+            RuntimeErrorKind::MaxCallDepth { .. } => "R0009",
+            RuntimeErrorKind::OutOfFuel { .. } => "R0010",
+            RuntimeErrorKind::OutOfMemory { .. } => "R0011",
+            RuntimeErrorKind::Cancelled => "R0012",
+            RuntimeErrorKind::Timeout { .. } => "R0013",
+            RuntimeErrorKind::HostFunction { .. } => "R0014",
+            RuntimeErrorKind::MaxNestingDepth { .. } => "R0015",
+        }
+    }
+
+    /// Sets the location, unless a more precise one is already known.
+    pub(crate) fn at(mut self, location: Location) -> Self {
+        self.location.get_or_insert(location);
+        self
+    }
+
+    /// Adds the call of `function`, through which the error was propagated.
+    pub(crate) fn called(mut self, function: &str, location: Location) -> Self {
+        self.stack.push(StackFrame { function: function.to_string(), location });
+        self
+    }
+}
+
+impl From<RuntimeErrorKind> for RuntimeError {
+    fn from(kind: RuntimeErrorKind) -> Self {
+        RuntimeError { kind, location: None, stack: Vec::new() }
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] ", self.code())?;
+        match &self.kind {
+            RuntimeErrorKind::VariableNotFound { name } => write!(f, "Variable {} not found.", name),
+            RuntimeErrorKind::InvalidOperands { lhs, operand, rhs } => write!(f, "Can not calculate {} {:?} {}", lhs, operand, rhs),
+            RuntimeErrorKind::NotFunction { name } => write!(f, "Can not call function {}.", name),
+            RuntimeErrorKind::ConditionNotBool { found } => write!(f, "Condition must be Bool. found:{}", found),
+            RuntimeErrorKind::Unimplemented { feature } => write!(f, "Unimplemented: {}", feature),
+            RuntimeErrorKind::InvalidArguments { function, found } => write!(f, "Can not call {} with ({})", function, found.join(",")),
+            RuntimeErrorKind::IntegerOverflow { operand } => write!(f, "Integer overflow in {:?}", operand),
+            RuntimeErrorKind::InvalidRange { start, end, len } => write!(f, "Range {}..{} is out of bounds of length {}", start, end, len),
+            RuntimeErrorKind::MaxCallDepth { depth } => write!(f, "Maximum call depth {} exceeded.", depth),
+            RuntimeErrorKind::MaxNestingDepth { depth } => write!(f, "Maximum expression nesting depth {} exceeded.", depth),
+            RuntimeErrorKind::OutOfFuel { fuel } => write!(f, "Out of fuel. fuel:{}", fuel),
+            RuntimeErrorKind::OutOfMemory { limit } => write!(f, "Out of memory. limit:{} bytes", limit),
+            RuntimeErrorKind::Cancelled => write!(f, "Cancelled."),
+            RuntimeErrorKind::Timeout { timeout } => write!(f, "Timeout. timeout:{:?}", timeout),
+            RuntimeErrorKind::HostFunction { function, message } => write!(f, "Host function {} failed: {}", function, message),
+        }?;
+        if let Some(l) = &self.location {
+            write!(f, " at {}", l)?
+        }
+        for frame in &self.stack {
+            write!(f, "\n    in {} called at {}", frame.function, frame.location)?
+        }
+        Ok(())
+    }
+}
+
+impl Error for RuntimeError {}
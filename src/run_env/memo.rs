@@ -0,0 +1,85 @@
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use crate::run_env::{DataObj, RefDataObj};
+use crate::std_library::{BoolType, FloatType, IntType, StdMod, StringType};
+
+/// Default maximum number of results cached by a memoized function value.
+pub const DEFAULT_MEMO_CAPACITY: usize = 1024;
+
+/// Parameters and result of a call.
+type Entry<S> = (Vec<RefDataObj<S>>, RefDataObj<S>);
+
+/// Call of a memoized function in progress: the cache and the parameters. The result is cached when the call ends.
+pub(crate) type MemoCall<S> = (Rc<Memo<S>>, Vec<RefDataObj<S>>);
+
+/// Results of a memoized function value, keyed by the values of the parameters.
+/// When the cache is full, new results are not cached.
+pub struct Memo<S: StdMod> {
+    capacity: usize,
+    len: Cell<usize>,
+    /// Results grouped by the hash of the parameters. Parameters with the same hash are compared with `equals`.
+    results: RefCell<HashMap<u64, Vec<Entry<S>>>>,
+}
+
+impl<S: StdMod> Memo<S> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Memo { capacity, len: Cell::new(0), results: RefCell::new(HashMap::new()) }
+    }
+
+    pub(crate) fn get(&self, params: &[RefDataObj<S>]) -> Option<RefDataObj<S>> {
+        let results = self.results.borrow();
+        let (_, value) = results.get(&hash(params))?.iter().find(|(key, _)| same(key, params))?;
+        Some(value.clone())
+    }
+
+    /// Tells if a call should be recorded, when `pending` calls are already waiting for their results.
+    /// This bounds the memory used by a long chain of tail calls.
+    pub(crate) fn has_room(&self, pending: usize) -> bool {
+        self.len.get() + pending < self.capacity
+    }
+
+    pub(crate) fn insert(&self, params: Vec<RefDataObj<S>>, value: RefDataObj<S>) {
+        if !self.has_room(0) {
+            return;
+        }
+        let mut results = self.results.borrow_mut();
+        let entries = results.entry(hash(&params)).or_default();
+        if !entries.iter().any(|(key, _)| same(key, &params)) {
+            entries.push((params, value));
+            self.len.set(self.len.get() + 1)
+        }
+    }
+}
+
+/// Parameters, which can not be compared, like functions, are never the same.
+fn same<S: StdMod>(l: &[RefDataObj<S>], r: &[RefDataObj<S>]) -> bool {
+    l.iter().zip(r).all(|(l, r)| l.borrow().equals(&r.borrow()) == Some(true))
+}
+
+fn hash<S: StdMod>(params: &[RefDataObj<S>]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for p in params {
+        hash_value(&p.borrow(), &mut hasher)
+    }
+    hasher.finish()
+}
+
+/// Hashes the parts of the value, which are the same for equal values.
+fn hash_value<S: StdMod>(value: &DataObj<S>, hasher: &mut DefaultHasher) {
+    match value {
+        DataObj::Int(v) => v.to_i32().hash(hasher),
+        DataObj::Float(v) => v.trunc().hash(hasher),
+        DataObj::String(v) => v.len().hash(hasher),
+        DataObj::Bool(v) => v.is_true().hash(hasher),
+        DataObj::Func(_) | DataObj::Empty(_) => (),
+        DataObj::Tuple(items) => {
+            for item in items {
+                hash_value(item, hasher)
+            }
+        }
+    }
+}
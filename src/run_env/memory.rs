@@ -0,0 +1,48 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use crate::run_env::error::{RuntimeError, RuntimeErrorKind};
+
+/// Approximate memory used by the live values of a `RunEnv`.
+pub struct Memory {
+    /// Maximum number of bytes, unlimited if `None`.
+    limit: Cell<Option<usize>>,
+    used: Cell<usize>,
+}
+
+impl Memory {
+    pub fn new(limit: Option<usize>) -> Self {
+        Memory { limit: Cell::new(limit), used: Cell::new(0) }
+    }
+
+    pub fn set_limit(&self, limit: Option<usize>) {
+        self.limit.set(limit)
+    }
+
+    pub fn used(&self) -> usize {
+        self.used.get()
+    }
+
+    /// Reserves `size` bytes until the returned allocation is dropped.
+    pub(crate) fn allocate(self: &Rc<Self>, size: usize) -> Result<Allocation, RuntimeError> {
+        let used = self.used.get() + size;
+        match self.limit.get() {
+            Some(limit) if used > limit => Err(RuntimeErrorKind::OutOfMemory { limit }.into()),
+            _ => {
+                self.used.set(used);
+                Ok(Allocation { memory: self.clone(), size })
+            }
+        }
+    }
+}
+
+pub struct Allocation {
+    memory: Rc<Memory>,
+    size: usize,
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        self.memory.used.set(self.memory.used.get() - self.size)
+    }
+}
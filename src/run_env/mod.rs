@@ -1,46 +1,219 @@
+use std::cell::{Cell, OnceCell};
 use std::collections::HashMap;
 use std::ops::Deref;
-
-use nom::Finish;
+use std::rc::Rc;
+use std::time::Duration;
 
 use crate::ast::{Module, VariableDef};
-use crate::ast::expr::{Expr, FunctionCallExpr, FunctionDef, IfExpr, Operand, PartialExpr, VariableExpr};
+use crate::ast::expr::{Expr, FunctionCallExpr, FunctionDef, IfExpr, Operand, PartialExpr, TupleDef, VariableExpr};
 use crate::ast::states::{Unverified, Verified};
+use crate::ast::location::Location;
+use crate::ast::symbol::{Symbol, SymbolTable};
+use crate::ast::types;
 use crate::ast::types::VariableType;
-use crate::ast::variable::{ConstantValue, VariableName};
-use crate::parser::expr::parse_expr;
-use crate::parser::parse_module;
-pub use crate::run_env::data_obj::{DataObj, RefDataObj};
-use crate::run_env::variable_stack::VariableStack;
-use crate::std_library::{BoolType, FuncType, IntType, StdMod};
+use crate::ast::variable::{Binding, ConstantValue, Link, VariableName, PATH_SEPARATOR};
+use crate::error::Error;
+use crate::parser::{parse_expression, parse_signature, parse_with};
+use crate::run_env::context::{CancelHandle, RunContext, DEFAULT_MAX_CALL_DEPTH, DEFAULT_MAX_NESTING_DEPTH};
+pub use crate::run_env::data_obj::{DataObj, DataRef, RefDataObj};
+use crate::run_env::error::{RuntimeError, RuntimeErrorKind};
+use crate::run_env::memo::DEFAULT_MEMO_CAPACITY;
+use crate::run_env::memory::Memory;
+use crate::run_env::variable_stack::{Globals, VariableStack};
+use crate::std_library::{BoolType, FloatType, FuncType, IntType, StdMod, StringType};
+use crate::verify::error::TypeError;
 use crate::verify::variable_mng::VariableManager;
+use crate::vm::{compile, machine};
 
 pub mod variable_stack;
+pub mod context;
 pub mod data_obj;
+pub mod error;
+pub mod memo;
+pub mod memory;
+
+/// Way of running the verified scripts. Both backends produce the same results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// Evaluates the verified AST.
+    #[default]
+    TreeWalker,
+    /// Compiles the verified AST to bytecode, which runs on a stack machine.
+    /// Script calls do not use the native stack.
+    Bytecode,
+}
 
 pub struct RunEnv<S: StdMod> {
+    backend: Backend,
+    optimize: bool,
     modules: HashMap<String, Module<Verified>>,
-    variable_stack: VariableStack<S>,
+    namespaces: HashMap<String, Globals<S>>,
+    max_call_depth: usize,
+    max_nesting_depth: usize,
+    fuel: Option<u64>,
+    fuel_used: Cell<u64>,
+    memory: Rc<Memory>,
+    cancel: CancelHandle,
+    timeout: Option<Duration>,
+    memo_capacity: usize,
+    /// Functions registered by the host: their types and values.
+    host_functions: HashMap<Symbol, (types::FuncType, RefDataObj<S>)>,
+    /// Table of the symbols of the modules, the host functions and the run expressions.
+    symbols: SymbolTable,
+}
+
+impl<S: StdMod> Default for RunEnv<S> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<S: StdMod> RunEnv<S> {
     pub fn new() -> Self {
-        Self { modules: HashMap::new(), variable_stack: VariableStack::new() }
+        Self {
+            backend: Backend::default(),
+            optimize: false,
+            modules: HashMap::new(),
+            namespaces: HashMap::new(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
+            fuel: None,
+            fuel_used: Cell::new(0),
+            memory: Rc::new(Memory::new(None)),
+            cancel: CancelHandle::default(),
+            timeout: None,
+            memo_capacity: DEFAULT_MEMO_CAPACITY,
+            host_functions: HashMap::new(),
+            symbols: SymbolTable::default(),
+        }
+    }
+
+    /// Sets the maximum depth of nested script function calls. Deeper calls stop the execution with a runtime error.
+    /// With the tree-walker, each call uses the native stack, so a larger limit may need a larger stack for the thread.
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth
+    }
+
+    /// Sets the maximum depth of the expressions evaluated by the tree-walker, counted over all the running calls.
+    /// Deeper expressions stop the execution with a runtime error. Each level uses the native stack,
+    /// so a larger limit may need a larger stack for the thread. The bytecode backend does not use this limit.
+    pub fn set_max_nesting_depth(&mut self, depth: usize) {
+        self.max_nesting_depth = depth
+    }
+
+    /// Sets the fuel of each execution: running a module when it is added, or running an expression.
+    /// Every evaluated expression and function call consumes one unit. `None` means unlimited fuel.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel
+    }
+
+    /// Fuel used by the last execution.
+    pub fn fuel_used(&self) -> u64 {
+        self.fuel_used.get()
+    }
+
+    /// Sets the maximum approximate size in bytes of the live values created by the scripts of the environment.
+    /// `None` means unlimited memory.
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+        self.memory.set_limit(limit)
+    }
+
+    /// Approximate size in bytes of the live values created by the scripts of the environment.
+    /// Ints, floats, bools and empty values are stored inline and are not counted.
+    pub fn memory_used(&self) -> usize {
+        self.memory.used()
+    }
+
+    /// Handle, which can stop the executions of the environment from another thread.
+    /// A cancelled execution returns a runtime error, and the environment can be used again afterwards.
+    /// A request made between the executions stops the next one.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
     }
 
-    pub fn add_module(&mut self, name: String, module: Module<Unverified>) -> Result<(), String> {
-        let m = module.verify()?;
-        self.variable_stack.extend(m.run())?;
+    /// Sets the maximum wall-clock time of each execution. `None` means no timeout.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout
+    }
+
+    /// Sets the backend of the later executions. Functions created by either backend can be called from the other.
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend
+    }
+
+    /// Sets the maximum number of results cached by each memoized function value (`memo <a:Int>:Int -> ...`)
+    /// created by the later executions. When the cache of a function is full, its new results are not cached.
+    pub fn set_memo_capacity(&mut self, capacity: usize) {
+        self.memo_capacity = capacity
+    }
+
+    /// Turns on the optimization of the later added modules and run expressions: constant folding,
+    /// removal of dead branches and inlining of constant module variables. Results do not change, but less fuel is used.
+    pub fn set_optimize(&mut self, optimize: bool) {
+        self.optimize = optimize
+    }
+
+    /// Registers a function implemented by the host. The later added modules and run expressions can call it by name
+    /// and use it as a value, like a script function. `signature` is its type in the script syntax: `<String>:()`.
+    /// It hides a builtin function with the same name, and is hidden by a module variable with the same name.
+    /// Each call is an evaluation step, and an error returned by the function stops the execution.
+    /// So does a result, which does not have the return type of `signature`.
+    pub fn register_fn(&mut self, name: &str, signature: &str, function: impl Fn(&[DataObj<S>]) -> Result<DataObj<S>, String> + 'static) -> Result<(), Error> {
+        let func_type = parse_signature(signature)?;
+        let name = self.symbols.intern(name);
+        let return_type = func_type.return_type.info.clone();
+        let checked = move |params: &[DataObj<S>]| {
+            let value = function(params)?;
+            if value.has_type(&return_type) {
+                Ok(value)
+            } else {
+                Err(format!("returned {} instead of {}", value.type_str(), return_type))
+            }
+        };
+        let value = S::func_obj(S::FUNC::host(name.clone(), Rc::new(checked))).into_ref();
+        self.host_functions.insert(name, (func_type, value));
+        Ok(())
+    }
+
+    /// Creates a manager, where the builtin and the host functions are visible.
+    fn variable_manager(&self) -> VariableManager {
+        let mut variable_mng = VariableManager::new(self.symbols.clone());
+        for (name, (func_type, _)) in &self.host_functions {
+            variable_mng.add_host(name.clone(), func_type.clone())
+        }
+        variable_mng
+    }
+
+    /// Creates the context of an execution starting now. The execution must be ended with `CancelHandle::finish`.
+    fn new_context(&self) -> Rc<RunContext> {
+        Rc::new(RunContext::new(self.max_call_depth, self.max_nesting_depth, self.fuel, self.memory.clone(), self.cancel.clone(), self.timeout, self.memo_capacity))
+    }
+
+    /// Verifies and runs the module. The module can use variables of the modules added before it.
+    /// Its symbols are moved to the table of the environment, unless it was parsed with it.
+    pub fn add_module(&mut self, name: String, module: Module<Unverified>) -> Result<(), Error> {
+        if self.modules.contains_key(&name) {
+            return Err(Error::DuplicateModule { name });
+        }
+        let mut m = module.intern_into(&self.symbols).verify_with(&self.modules, self.variable_manager())?;
+        if self.optimize {
+            m = m.optimize::<S>();
+        }
+        let context = self.new_context();
+        let mut variable_stack = VariableStack::with_context(self.link(m.variables.len(), &m.links), context.clone());
+        let result = m.run(&mut variable_stack, self.backend);
+        self.cancel.finish();
+        self.fuel_used.set(context.fuel_used());
+        result?;
+        self.namespaces.insert(name.clone(), variable_stack.globals());
         self.modules.insert(name, m);
 
         Ok(())
     }
 
-    pub fn parse_and_add(&mut self, name: String, code: &str) -> Result<(), String> {
-        let (unparsed, module) = parse_module(code).finish().map_err(|e| format!("{:?}", e))?;
-        if unparsed.len() > 0 {
-            return Err(format!("unparsed: {}", unparsed));
-        } else { self.add_module(name, module) }
+    pub fn parse_and_add(&mut self, name: String, code: &str) -> Result<(), Error> {
+        let module = parse_with(code, &self.symbols)?;
+        self.add_module(name, module)
     }
 
     pub fn print(&self) {
@@ -50,128 +223,240 @@ impl<S: StdMod> RunEnv<S> {
         }
     }
 
+    /// The verified module, as it is run. Its `Display` output shows the result of the optimization.
+    pub fn module(&self, name: &str) -> Option<&Module<Verified>> {
+        self.modules.get(name)
+    }
+
+    /// Finds a public variable with a qualified name: `module_name::variable`.
     pub fn find_variable(&self, name: &str) -> Option<RefDataObj<S>> {
-        self.variable_stack.try_find_variable(name)
+        let (module, name) = name.split_once(PATH_SEPARATOR)?;
+        let name = self.symbols.find(name)?;
+        self.modules.get(module)?.variables.iter().position(|v| v.public && v.name == name)
+            .and_then(|index| self.namespaces[module][index].get().cloned())
     }
 
-    pub fn run(&self, expr: &str) -> Result<(VariableType, RefDataObj<S>), String> {
-        let (_, e) = parse_expr(expr).map_err(|e| e.to_string())?;
-        //println!("{}",e);
-        let mut v_mng = VariableManager::new();
-        for (_, m) in &self.modules {
-            v_mng.add_module(m)
+    /// Runs the expression. Public variables of the modules must be referred with qualified names: `module_name::variable`.
+    pub fn run(&self, expr: &str) -> Result<(VariableType, RefDataObj<S>), Error> {
+        let e = parse_expression(expr, &self.symbols)?;
+        let mut v_mng = self.variable_manager();
+        for (name, m) in &self.modules {
+            v_mng.add_module(name, m)
+        }
+        let mut e = e.check_type(&mut v_mng).map_err(TypeError::renumbered)?;
+        v_mng.check_operands().map_err(TypeError::renumbered)?;
+        e.map_types(&mut |vt| v_mng.resolve_variable(vt));
+        e.infer_effects(&v_mng);
+        e.check_pure()?;
+        if self.optimize {
+            e.optimize::<S>();
         }
-        let t = e.check_type(&mut v_mng)?;
-        let r = e.run(&self.variable_stack);
-        Ok((t, r))
+        let t = e.v_type.clone();
+        let context = self.new_context();
+        let r = e.run_with(self.backend, &VariableStack::with_context(self.link(0, &v_mng.links()), context.clone()));
+        self.cancel.finish();
+        self.fuel_used.set(context.fuel_used());
+        Ok((t, r?))
+    }
+
+    /// Creates globals with `variables` empty slots for the variables of a module, followed by the linked variables.
+    fn link(&self, variables: usize, links: &[Link]) -> Globals<S> {
+        let linked = links.iter().map(|link| match link {
+            Link::Module { module, name } => {
+                let module = module.name();
+                let index = self.modules[&*module].variables.iter().position(|v| &v.name == name).expect("verified link");
+                self.namespaces[&*module][index].get().expect("variable of an added module").clone()
+            }
+            Link::Host(name) => self.host_functions[name].1.clone()
+        });
+        (0..variables).map(|_| OnceCell::new()).chain(linked.map(OnceCell::from)).collect()
     }
 }
 
 impl Module<Verified> {
-    fn run<S: StdMod>(&self) -> VariableStack<S> {
-        let mut known_variables = VariableStack::new();
-        for VariableName { name, variable: VariableDef { value: expr, v_type: _ } } in &self.variables {
-            let r: RefDataObj<S> = expr.run(&known_variables);
-            known_variables.add_variable(name.clone(), r);
+    fn run<S: StdMod>(&self, known_variables: &mut VariableStack<S>, backend: Backend) -> Result<(), RuntimeError> {
+        for (index, VariableName { location, variable: VariableDef { value: expr, .. }, .. }) in self.variables.iter().enumerate() {
+            let r: RefDataObj<S> = expr.run_with(backend, known_variables).map_err(|e| e.at(*location))?;
+            known_variables.set_global(index, r);
         }
-        known_variables
+        Ok(())
     }
 }
 
-fn calculate<S: StdMod>(lhs: &DataObj<S>, o: &Operand, rhs: &DataObj<S>) -> DataObj<S> {
+pub(crate) fn calculate<S: StdMod>(lhs: &DataObj<S>, o: &Operand, rhs: &DataObj<S>) -> Result<DataObj<S>, RuntimeError> {
     match (lhs, o, rhs) {
-        (DataObj::Int(l), Operand::Plus, DataObj::Int(r)) => S::int_obj(l.plus(r)),
-        (DataObj::Int(l), Operand::Minus, DataObj::Int(r)) => S::int_obj(l.minus(r)),
-        (DataObj::Int(l), Operand::Lt, DataObj::Int(r)) => S::bool_obj(l.lt(r)),
-        (_, _, _) => panic!("Runtime ERROR: Can not calculate {} {:?} {}", lhs.type_str(), o, rhs.type_str())
+        (DataObj::Int(l), Operand::Plus, DataObj::Int(r)) => l.plus(r).map(S::int_obj).ok_or_else(|| overflow(o)),
+        (DataObj::Int(l), Operand::Minus, DataObj::Int(r)) => l.minus(r).map(S::int_obj).ok_or_else(|| overflow(o)),
+        (DataObj::Float(l), Operand::Plus, DataObj::Float(r)) => Ok(S::float_obj(l.plus(r))),
+        (DataObj::Float(l), Operand::Minus, DataObj::Float(r)) => Ok(S::float_obj(l.minus(r))),
+        (DataObj::String(l), Operand::Plus, DataObj::String(r)) => Ok(S::string_obj(l.concat(r))),
+        (_, Operand::Lt, _) => lhs.less_than(rhs).map(S::bool_create).ok_or_else(|| invalid_operands(lhs, o, rhs)),
+        (_, Operand::Eq, _) => lhs.equals(rhs).map(S::bool_create).ok_or_else(|| invalid_operands(lhs, o, rhs)),
+        (_, Operand::Ne, _) => lhs.equals(rhs).map(|eq| S::bool_create(!eq)).ok_or_else(|| invalid_operands(lhs, o, rhs)),
+        (_, _, _) => Err(invalid_operands(lhs, o, rhs))
     }
 }
 
-impl Expr {
-    pub(crate) fn run<S: StdMod>(&self, known_variables: &VariableStack<S>) -> RefDataObj<S> {
-        if self.operands.len() == 0 {
+fn invalid_operands<S: StdMod>(lhs: &DataObj<S>, o: &Operand, rhs: &DataObj<S>) -> RuntimeError {
+    RuntimeErrorKind::InvalidOperands { lhs: lhs.type_str().to_string(), operand: *o, rhs: rhs.type_str().to_string() }.into()
+}
+
+/// Result of running the body of a function: a value, or a call in tail position, which the caller must make.
+pub(crate) enum Flow<S: StdMod> {
+    Value(RefDataObj<S>),
+    TailCall { function: RefDataObj<S>, params: Vec<RefDataObj<S>>, name: Symbol, location: Location },
+}
+
+fn overflow(o: &Operand) -> RuntimeError {
+    RuntimeErrorKind::IntegerOverflow { operand: *o }.into()
+}
+
+impl Expr<Verified> {
+    fn run_with<S: StdMod>(&self, backend: Backend, known_variables: &VariableStack<S>) -> Result<RefDataObj<S>, RuntimeError> {
+        match backend {
+            Backend::TreeWalker => self.run(known_variables),
+            Backend::Bytecode => machine::run(compile(self), known_variables)
+        }
+    }
+
+    pub(crate) fn run<S: StdMod>(&self, known_variables: &VariableStack<S>) -> Result<RefDataObj<S>, RuntimeError> {
+        let context = known_variables.context();
+        context.step()
+            .and_then(|_| {
+                let _nesting = context.enter_expr()?;
+                self.run_operands(known_variables)
+            })
+            .map_err(|e| e.at(self.location))
+    }
+
+    /// Runs the body of a function. Tail calls are returned instead of made, so that they use no native stack.
+    pub(crate) fn run_body<S: StdMod>(&self, known_variables: &VariableStack<S>) -> Result<Flow<S>, RuntimeError> {
+        let context = known_variables.context();
+        context.step()
+            .and_then(|_| {
+                let _nesting = context.enter_expr()?;
+                match self.exprs.as_slice() {
+                    [PartialExpr::FunctionCall(call)] if call.tail => call.tail_call(known_variables),
+                    [PartialExpr::If(e)] if self.operands.is_empty() => e.run_body(known_variables),
+                    _ => self.run_operands(known_variables).map(Flow::Value)
+                }
+            })
+            .map_err(|e| e.at(self.location))
+    }
+
+    fn run_operands<S: StdMod>(&self, known_variables: &VariableStack<S>) -> Result<RefDataObj<S>, RuntimeError> {
+        if self.operands.is_empty() {
             self.exprs.first().unwrap().run(known_variables)
         } else {
             let mut it = self.exprs.iter();
-            let mut value = it.next().unwrap().run(known_variables).borrow().deref().clone();
+            let mut value = it.next().unwrap().run(known_variables)?.borrow().deref().clone();
             for (e, o) in it.zip(&self.operands) {
-                value = calculate(&value, o, &e.run(known_variables).borrow().deref().clone());
+                value = calculate(&value, o, &e.run(known_variables)?.borrow())?;
             }
-            value.into_ref()
+            known_variables.context().alloc(value)
         }
     }
 }
 
-impl PartialExpr {
-    fn run<S: StdMod>(&self, known_variables: &VariableStack<S>) -> RefDataObj<S> {
+impl PartialExpr<Verified> {
+    fn run<S: StdMod>(&self, known_variables: &VariableStack<S>) -> Result<RefDataObj<S>, RuntimeError> {
         match self {
-            PartialExpr::Block(_) => todo!(),
+            PartialExpr::Block(_) => Err(RuntimeErrorKind::Unimplemented { feature: "block".to_string() }.into()),
             PartialExpr::If(v) => v.run(known_variables),
             PartialExpr::FunctionCall(v) => v.run(known_variables),
             PartialExpr::Variable(v) => v.run(known_variables),
             PartialExpr::Lambda(v) => v.run(known_variables),
-            PartialExpr::Tuple(_) => todo!(),
+            PartialExpr::Tuple(v) => v.run(known_variables),
         }
     }
 }
 
-impl VariableExpr {
-    fn run<S: StdMod>(&self, known_variables: &VariableStack<S>) -> RefDataObj<S> {
+impl VariableExpr<Verified> {
+    fn run<S: StdMod>(&self, known_variables: &VariableStack<S>) -> Result<RefDataObj<S>, RuntimeError> {
         match self {
-            VariableExpr::Variable(name) => known_variables.find_variable(name),
-            VariableExpr::Constant(v) => match v {
+            VariableExpr::Variable { name, location, binding, .. } => known_variables.find_variable(name, binding).map_err(|e| e.at(*location)),
+            VariableExpr::Constant(v) => known_variables.context().alloc(match v {
                 ConstantValue::Integer(v) => S::int_create(*v),
                 ConstantValue::Float(v) => S::float_create(*v),
                 ConstantValue::String(v) => S::string_create(v.clone())
-            }.into_ref()
+            })
         }
     }
 }
 
-impl FunctionDef {
-    fn run<S: StdMod>(&self, known_variables: &VariableStack<S>) -> RefDataObj<S> {
-        let params = self.parameters.iter().map(|(name, _)| name.clone()).collect();
-
-        let mut closure = HashMap::new();
-        for (name, _) in &self.closure {
-            let v = known_variables.find_variable(name);
-            closure.insert(name.clone(), v);
-        };
-        S::func_create(self.expr.clone(), params, closure).into_ref()
+impl FunctionDef<Verified> {
+    fn run<S: StdMod>(&self, known_variables: &VariableStack<S>) -> Result<RefDataObj<S>, RuntimeError> {
+        let closure = self.closure.iter()
+            .map(|c| known_variables.find_variable(&c.name, &c.binding).map_err(|e| e.at(self.location)))
+            .collect::<Result<_, _>>()?;
+        let context = known_variables.context();
+        let memo = self.memo.then(|| context.memo());
+        context.alloc(S::func_create(self.expr.clone(), self.uses_self, closure, known_variables.globals(), memo))
     }
 }
 
-impl FunctionCallExpr {
-    fn run<S: StdMod>(&self, known_variables: &VariableStack<S>) -> RefDataObj<S> {
-        let ref_obj = known_variables.find_variable(&self.name);
-        let mut closure_variables = VariableStack::new();
-        closure_variables.add_variable("self_fn".to_string(), ref_obj.clone());
-
+impl FunctionCallExpr<Verified> {
+    fn run<S: StdMod>(&self, known_variables: &VariableStack<S>) -> Result<RefDataObj<S>, RuntimeError> {
+        if let Binding::Builtin(builtin) = self.binding {
+            let params = self.params.iter()
+                .map(|e| e.run(known_variables).map(|p| p.borrow().deref().clone()))
+                .collect::<Result<Vec<_>, _>>()?;
+            return builtin.call(&params).and_then(|v| known_variables.context().alloc(v)).map_err(|e| e.at(self.location));
+        }
+        let ref_obj = known_variables.find_variable(&self.name, &self.binding).map_err(|e| e.at(self.location))?;
         let obj = ref_obj.borrow();
         match obj.deref() {
             DataObj::Func(f) => {
-                let p = self.params.iter().map(|e| e.run(known_variables)).collect();
-                f.call(p)
+                let p = self.params.iter().map(|e| e.run(known_variables)).collect::<Result<_, _>>()?;
+                let _call = known_variables.context().enter_call().map_err(|e| e.at(self.location))?;
+                f.call(p, known_variables.context().clone()).map_err(|e| e.called(&self.name.name(), self.location))
             }
-            _ => panic!("Runtime ERROR: Can not call function {}.", self.name)
+            _ => Err(RuntimeError::from(RuntimeErrorKind::NotFunction { name: self.name.to_string() }).at(self.location))
         }
     }
+
+    /// Evaluates the function and the parameters, but leaves the call to the caller.
+    fn tail_call<S: StdMod>(&self, known_variables: &VariableStack<S>) -> Result<Flow<S>, RuntimeError> {
+        let function = known_variables.find_variable(&self.name, &self.binding).map_err(|e| e.at(self.location))?;
+        let params = self.params.iter().map(|e| e.run(known_variables)).collect::<Result<_, _>>()?;
+        Ok(Flow::TailCall { function, params, name: self.name.clone(), location: self.location })
+    }
 }
 
-impl IfExpr {
-    fn run<S: StdMod>(&self, known_variables: &VariableStack<S>) -> RefDataObj<S> {
-        match self.cond_expr.run(known_variables).borrow().deref() {
-            DataObj::Bool(cond) => {
-                if cond.is_true() {
-                    self.main_branch.run(known_variables)
-                } else {
-                    match &self.else_branch {
-                        None => S::empty_create().into_ref(),
-                        Some(e) => e.run(known_variables)
-                    }
-                }
-            }
-            _ => panic!("runtime ERROR: can not get bool")
+impl TupleDef<Verified> {
+    fn run<S: StdMod>(&self, known_variables: &VariableStack<S>) -> Result<RefDataObj<S>, RuntimeError> {
+        if self.items.is_empty() {
+            return known_variables.context().alloc(S::empty_create());
+        }
+        let items = self.items.iter()
+            .map(|e| e.run(known_variables).map(|v| v.borrow().deref().clone()))
+            .collect::<Result<_, _>>()?;
+        known_variables.context().alloc(DataObj::Tuple(items))
+    }
+}
+
+impl IfExpr<Verified> {
+    fn run<S: StdMod>(&self, known_variables: &VariableStack<S>) -> Result<RefDataObj<S>, RuntimeError> {
+        match self.branch(known_variables)? {
+            Some(e) => e.run(known_variables),
+            None => known_variables.context().alloc(S::empty_create())
+        }
+    }
+
+    fn run_body<S: StdMod>(&self, known_variables: &VariableStack<S>) -> Result<Flow<S>, RuntimeError> {
+        match self.branch(known_variables)? {
+            Some(e) => e.run_body(known_variables),
+            None => known_variables.context().alloc(S::empty_create()).map(Flow::Value)
         }
     }
-}
\ No newline at end of file
+
+    /// Evaluates the condition and returns the branch to run, `None` if there is no else branch to run.
+    fn branch<S: StdMod>(&self, known_variables: &VariableStack<S>) -> Result<Option<&Expr<Verified>>, RuntimeError> {
+        let cond = self.cond_expr.run(known_variables)?;
+        let cond = cond.borrow();
+        match cond.deref() {
+            DataObj::Bool(cond) => Ok(if cond.is_true() { Some(&self.main_branch) } else { self.else_branch.as_ref() }),
+            v => Err(RuntimeError::from(RuntimeErrorKind::ConditionNotBool { found: v.type_str().to_string() }).at(self.cond_expr.location))
+        }
+    }
+}
@@ -1,55 +1,71 @@
-use std::collections::HashMap;
+use std::cell::OnceCell;
+use std::rc::Rc;
+
+use crate::ast::symbol::Symbol;
+use crate::ast::variable::Binding;
+use crate::run_env::context::{CancelHandle, RunContext, DEFAULT_MAX_CALL_DEPTH, DEFAULT_MAX_NESTING_DEPTH};
+use crate::run_env::error::{RuntimeError, RuntimeErrorKind};
+use crate::run_env::memo::DEFAULT_MEMO_CAPACITY;
+use crate::run_env::memory::Memory;
 use crate::run_env::RefDataObj;
 use crate::std_library::StdMod;
 
-pub struct VariableStack<S: StdMod>{
-    stack: Vec<HashMap<String, RefDataObj<S>>>
+/// Global variables of a module, indexed with their slots. They are shared with every function created in the module,
+/// so functions can call each other regardless of the definition order. A variable of the module is set when it is run,
+/// the linked ones when the module is added.
+pub type Globals<S> = Rc<[OnceCell<RefDataObj<S>>]>;
+
+pub struct VariableStack<S: StdMod> {
+    globals: Globals<S>,
+    context: Rc<RunContext>,
+    /// Frame of the running function, indexed with the slots of the local variables.
+    locals: Vec<RefDataObj<S>>,
 }
 
-impl<S: StdMod> VariableStack<S> {
+impl<S: StdMod> Default for VariableStack<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-    pub fn new()->Self{
-        Self{stack: vec![HashMap::new()]}
+impl<S: StdMod> VariableStack<S> {
+    pub fn new() -> Self {
+        Self::with_globals(Rc::new([]))
     }
 
-    pub fn add_variable(&mut self, name:String, v: RefDataObj<S>){
-        self.stack.last_mut().unwrap().insert(name, v);
+    pub fn with_globals(globals: Globals<S>) -> Self {
+        Self::with_context(globals, Rc::new(RunContext::new(DEFAULT_MAX_CALL_DEPTH, DEFAULT_MAX_NESTING_DEPTH, None, Rc::new(Memory::new(None)), CancelHandle::default(), None, DEFAULT_MEMO_CAPACITY)))
     }
 
-    pub fn find_variable(&self, name:&str) -> RefDataObj<S>{
-        self.try_find_variable(name).expect(&format!("Runtime ERROR: Variable {} not found.",name))
+    pub fn with_context(globals: Globals<S>, context: Rc<RunContext>) -> Self {
+        Self::with_locals(globals, context, Vec::new())
     }
 
-    pub fn try_find_variable(&self, name:&str) -> Option<RefDataObj<S>>{
-        for layer in self.stack.iter().rev(){
-            match layer.get(name) {
-                Some(d) => return Some(d.clone()),
-                _ => ()
-            };
-        };
-        None
+    pub fn with_locals(globals: Globals<S>, context: Rc<RunContext>, locals: Vec<RefDataObj<S>>) -> Self {
+        Self { globals, context, locals }
     }
 
-    pub fn add_layer(&mut self) {
-        self.stack.push(HashMap::new())
+    pub fn globals(&self) -> Globals<S> {
+        self.globals.clone()
     }
 
-    pub fn pop_layer(&mut self) {
-        self.stack.pop();
-        if self.stack.is_empty() {
-            self.add_layer()
-        }
+    pub fn context(&self) -> &Rc<RunContext> {
+        &self.context
     }
 
-    pub fn extend(&mut self, mut other: VariableStack<S>)->Result<(),String>{
-        if other.stack.len() != 1{
-            Err(format!("VariableStack should have only 1 layer. Found: {}",other.stack.len()))
-        }else {
-            for (name,obj) in other.stack.pop().unwrap(){
-                self.stack[0].insert(name, obj);
-            };
-            Ok(())
+    /// Sets the global variable in the slot, which is not set yet.
+    pub fn set_global(&mut self, index: usize, v: RefDataObj<S>) {
+        if self.globals[index].set(v).is_err() {
+            panic!("global {} is already set", index)
         }
     }
 
-}
\ No newline at end of file
+    /// Variables are found with their slots. `name` is reported, if the variable is not set yet.
+    pub fn find_variable(&self, name: &Symbol, binding: &Binding) -> Result<RefDataObj<S>, RuntimeError> {
+        match binding {
+            Binding::Local { slot } => self.locals.get(*slot),
+            Binding::Global { index } | Binding::Host { index } => self.globals[*index].get(),
+            Binding::Builtin(_) => None
+        }.cloned().ok_or_else(|| RuntimeErrorKind::VariableNotFound { name: name.to_string() }.into())
+    }
+}
@@ -1,10 +1,21 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::num::{Saturating, Wrapping};
+use std::ops::Deref;
+use std::rc::Rc;
 
 use crate::ast::expr::Expr;
-use crate::run_env::RefDataObj;
-use crate::run_env::variable_stack::VariableStack;
-use crate::std_library::{BoolType, EmptyType, FloatType, FuncType, IntType, StdMod, StringType};
+use crate::ast::states::Verified;
+use crate::ast::symbol::Symbol;
+use crate::run_env::context::RunContext;
+use crate::run_env::memo::{Memo, MemoCall};
+use crate::run_env::error::{RuntimeError, RuntimeErrorKind};
+use crate::run_env::{DataObj, Flow, RefDataObj};
+use crate::run_env::variable_stack::{Globals, VariableStack};
+use crate::std_library::{BoolType, EmptyType, FloatType, FuncType, HostFn, IntType, StdMod, StringType};
+use crate::vm::{CallSite, Closure};
+use crate::vm::machine;
 
+/// Integer overflow is a runtime error.
 #[derive(Clone)]
 pub struct StdModBasic {}
 
@@ -12,7 +23,33 @@ impl StdMod for StdModBasic {
     type INT = i32;
     type FLOAT = f32;
     type STRING = String;
-    type FUNC = FuncObj;
+    type FUNC = FuncObj<Self>;
+    type BOOL = bool;
+    type EMPTY = ();
+}
+
+/// Integer arithmetic wraps around on overflow.
+#[derive(Clone)]
+pub struct StdModWrapping {}
+
+impl StdMod for StdModWrapping {
+    type INT = Wrapping<i32>;
+    type FLOAT = f32;
+    type STRING = String;
+    type FUNC = FuncObj<Self>;
+    type BOOL = bool;
+    type EMPTY = ();
+}
+
+/// Integer arithmetic saturates at the bounds of `i32` on overflow.
+#[derive(Clone)]
+pub struct StdModSaturating {}
+
+impl StdMod for StdModSaturating {
+    type INT = Saturating<i32>;
+    type FLOAT = f32;
+    type STRING = String;
+    type FUNC = FuncObj<Self>;
     type BOOL = bool;
     type EMPTY = ();
 }
@@ -24,17 +61,93 @@ impl IntType for i32 {
         number
     }
 
-    fn plus(&self, other: &Self) -> Self {
-        self + other
+    fn plus(&self, other: &Self) -> Option<Self> {
+        self.checked_add(*other)
     }
 
-    fn minus(&self, other: &Self) -> Self {
-        self - other
+    fn minus(&self, other: &Self) -> Option<Self> {
+        self.checked_sub(*other)
+    }
+
+    fn lt(&self, other: &Self) -> Self::BOOL {
+        self < other
+    }
+
+    fn eq(&self, other: &Self) -> Self::BOOL {
+        self == other
+    }
+
+    fn to_i32(&self) -> i32 {
+        *self
+    }
+
+    fn to_float(&self) -> f32 {
+        *self as f32
+    }
+}
+
+impl IntType for Wrapping<i32> {
+    type BOOL = bool;
+
+    fn create(number: i32) -> Self {
+        Wrapping(number)
+    }
+
+    fn plus(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn minus(&self, other: &Self) -> Option<Self> {
+        Some(self - other)
     }
 
     fn lt(&self, other: &Self) -> Self::BOOL {
         self < other
     }
+
+    fn eq(&self, other: &Self) -> Self::BOOL {
+        self == other
+    }
+
+    fn to_i32(&self) -> i32 {
+        self.0
+    }
+
+    fn to_float(&self) -> f32 {
+        self.0 as f32
+    }
+}
+
+impl IntType for Saturating<i32> {
+    type BOOL = bool;
+
+    fn create(number: i32) -> Self {
+        Saturating(number)
+    }
+
+    fn plus(&self, other: &Self) -> Option<Self> {
+        Some(*self + *other)
+    }
+
+    fn minus(&self, other: &Self) -> Option<Self> {
+        Some(*self - *other)
+    }
+
+    fn lt(&self, other: &Self) -> Self::BOOL {
+        self < other
+    }
+
+    fn eq(&self, other: &Self) -> Self::BOOL {
+        self == other
+    }
+
+    fn to_i32(&self) -> i32 {
+        self.0
+    }
+
+    fn to_float(&self) -> f32 {
+        self.0 as f32
+    }
 }
 
 impl FloatType for f32 {
@@ -55,39 +168,203 @@ impl FloatType for f32 {
     fn lt(&self, other: &Self) -> Self::BOOL {
         self < other
     }
+
+    fn eq(&self, other: &Self) -> Self::BOOL {
+        self == other
+    }
+
+    fn trunc(&self) -> i32 {
+        *self as i32
+    }
+
+    fn round(&self) -> i32 {
+        f32::round(*self) as i32
+    }
+
+    fn to_f32(&self) -> f32 {
+        *self
+    }
 }
 
 impl StringType for String {
+    type BOOL = bool;
+
     fn create(txt: String) -> Self {
         txt
     }
+
+    fn as_str(&self) -> &str {
+        self
+    }
+
+    fn concat(&self, other: &Self) -> Self {
+        self.clone() + other
+    }
+
+    fn lt(&self, other: &Self) -> Self::BOOL {
+        self < other
+    }
+
+    fn eq(&self, other: &Self) -> Self::BOOL {
+        self == other
+    }
+
+    fn len(&self) -> i32 {
+        self.chars().count() as i32
+    }
+
+    fn substring(&self, start: i32, end: i32) -> Option<Self> {
+        if start < 0 || end < start || end > StringType::len(self) {
+            return None;
+        }
+        Some(self.chars().skip(start as usize).take((end - start) as usize).collect())
+    }
+
+    fn contains(&self, other: &Self) -> Self::BOOL {
+        str::contains(self, other.as_str())
+    }
+
+    fn split(&self, separator: &Self) -> (Self, Self) {
+        match self.split_once(separator.as_str()) {
+            Some((first, second)) => (first.to_string(), second.to_string()),
+            None => (self.clone(), String::new())
+        }
+    }
+
+    fn trim(&self) -> Self {
+        str::trim(self).to_string()
+    }
+
+    fn to_upper(&self) -> Self {
+        self.to_uppercase()
+    }
+
+    fn to_lower(&self) -> Self {
+        self.to_lowercase()
+    }
+
+    fn parse_int(&self) -> Option<i32> {
+        self.parse().ok()
+    }
+
+    fn parse_float(&self) -> Option<f32> {
+        self.parse().ok()
+    }
 }
 
+/// Function value. Cloning it only clones a reference to the shared body and captured variables.
 #[derive(Clone)]
-pub struct FuncObj {
-    expr: Expr,
-    params: Vec<String>,
-    closure: HashMap<String, RefDataObj<StdModBasic>>,
+pub struct FuncObj<S: StdMod> {
+    body: Rc<FuncBody<S>>,
+}
+
+enum FuncBody<S: StdMod> {
+    /// Function of the tree-walking interpreter.
+    Tree {
+        expr: Rc<Expr<Verified>>,
+        uses_self: bool,
+        closure: Vec<RefDataObj<S>>,
+        globals: Globals<S>,
+        memo: Option<Rc<Memo<S>>>,
+    },
+    /// Function of the bytecode backend.
+    Compiled(Closure<S>),
+    /// Function registered by the host.
+    Host { name: Symbol, function: HostFn<S> },
 }
 
-impl FuncType for FuncObj {
-    type S = StdModBasic;
+impl<S: StdMod<FUNC=Self>> FuncType for FuncObj<S> {
+    type S = S;
 
-    fn create(expr: Expr, params: Vec<String>, closure: HashMap<String, RefDataObj<Self::S>>) -> Self {
-        Self { expr, params, closure }
+    fn create(expr: Rc<Expr<Verified>>, uses_self: bool, closure: Vec<RefDataObj<Self::S>>, globals: Globals<Self::S>, memo: Option<Rc<Memo<Self::S>>>) -> Self {
+        Self { body: Rc::new(FuncBody::Tree { expr, uses_self, closure, globals, memo }) }
     }
 
-    fn call(&self, params: Vec<RefDataObj<Self::S>>) -> RefDataObj<Self::S> {
-        let mut variables = VariableStack::new();
-        variables.add_variable("self_fn".to_string(), Self::S::func_obj((*self).clone()).into_ref());
-        for (name, obj) in self.params.iter().zip(params) {
-            variables.add_variable(name.clone(), obj);
-        };
-        for (name, obj) in &self.closure {
-            variables.add_variable(name.clone(), obj.clone())
+    fn compiled(closure: Closure<Self::S>) -> Self {
+        Self { body: Rc::new(FuncBody::Compiled(closure)) }
+    }
+
+    fn host(name: Symbol, function: HostFn<Self::S>) -> Self {
+        Self { body: Rc::new(FuncBody::Host { name, function }) }
+    }
+
+    fn as_compiled(&self) -> Option<&Closure<Self::S>> {
+        match self.body.as_ref() {
+            FuncBody::Compiled(closure) => Some(closure),
+            FuncBody::Tree { .. } | FuncBody::Host { .. } => None
+        }
+    }
+
+    fn call(&self, params: Vec<RefDataObj<Self::S>>, context: Rc<RunContext>) -> Result<RefDataObj<Self::S>, RuntimeError> {
+        self.run_chain(params, context, Vec::new(), None)
+    }
+
+    fn tail_call(&self, params: Vec<RefDataObj<Self::S>>, context: Rc<RunContext>, memos: Vec<MemoCall<Self::S>>, site: CallSite) -> Result<RefDataObj<Self::S>, RuntimeError> {
+        self.run_chain(params, context, memos, Some(site))
+    }
+}
+
+impl<S: StdMod<FUNC=Self>> FuncObj<S> {
+    /// Tail calls of the body are made in a loop, so they run in constant native stack space.
+    /// The results of the memoized functions called in the loop are cached at the end.
+    fn run_chain(&self, params: Vec<RefDataObj<S>>, context: Rc<RunContext>, memos: Vec<MemoCall<S>>, tail_call: Option<CallSite>) -> Result<RefDataObj<S>, RuntimeError> {
+        let mut function = Cow::Borrowed(self);
+        let mut params = params;
+        let mut tail_call = tail_call;
+        let mut memos = memos;
+        let value = loop {
+            if let FuncBody::Tree { memo: Some(memo), .. } = function.body.as_ref() {
+                if let Some(value) = memo.get(&params) {
+                    break value;
+                }
+                if memo.has_room(memos.len()) {
+                    memos.push((memo.clone(), params.clone()));
+                }
+            }
+            let flow = function.run_body(params, &context, &mut memos, tail_call)?;
+            match flow {
+                Flow::Value(v) => break v,
+                Flow::TailCall { function: f, params: p, name, location } => {
+                    let next = match f.borrow().deref() {
+                        DataObj::Func(f) => f.clone(),
+                        _ => return Err(RuntimeError::from(RuntimeErrorKind::NotFunction { name: name.to_string() }).at(location))
+                    };
+                    function = Cow::Owned(next);
+                    params = p;
+                    tail_call = Some(CallSite { name, location });
+                }
+            }
         };
+        for (memo, params) in memos {
+            memo.insert(params, value.clone())
+        }
+        Ok(value)
+    }
 
-        self.expr.run(&variables)
+    /// `memos` are the calls of memoized functions waiting for the result, which the bytecode backend can add to.
+    /// Errors are reported as made by the last tail call `tail_call`.
+    fn run_body(&self, params: Vec<RefDataObj<S>>, context: &Rc<RunContext>, memos: &mut Vec<MemoCall<S>>, tail_call: Option<CallSite>) -> Result<Flow<S>, RuntimeError> {
+        let flow = match self.body.as_ref() {
+            FuncBody::Tree { expr, uses_self, closure, globals, .. } => context.step().and_then(|_| {
+                let mut locals = params;
+                locals.extend(closure.iter().cloned());
+                if *uses_self {
+                    locals.push(context.alloc(S::func_obj(self.clone()))?);
+                }
+                expr.run_body(&VariableStack::with_locals(globals.clone(), context.clone(), locals))
+            }),
+            FuncBody::Compiled(_) => return machine::call(self, params, context, memos, tail_call),
+            FuncBody::Host { name, function } => context.step().and_then(|_| {
+                let params: Vec<_> = params.iter().map(|p| p.borrow().deref().clone()).collect();
+                let value = function(&params)
+                    .map_err(|message| RuntimeError::from(RuntimeErrorKind::HostFunction { function: name.to_string(), message }))?;
+                context.alloc(value).map(Flow::Value)
+            })
+        };
+        flow.map_err(|e| match tail_call {
+            Some(site) => e.called(&site.name.name(), site.location),
+            None => e
+        })
     }
 }
 
@@ -102,8 +379,5 @@ impl BoolType for bool {
 }
 
 impl EmptyType for () {
-    fn create() -> Self {
-        ()
-    }
+    fn create() -> Self {}
 }
-
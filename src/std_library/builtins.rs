@@ -0,0 +1,116 @@
+use crate::ast::types::{FuncType, TypeInfo, VariableType};
+use crate::run_env::DataObj;
+use crate::run_env::error::{RuntimeError, RuntimeErrorKind};
+use crate::std_library::{FloatType, IntType, StdMod, StringType};
+use crate::verify::{BOOL_TYPE, FLOAT_TYPE, INT_TYPE, STRING_TYPE};
+
+/// Functions provided by the interpreter. They are visible in every module, unless a variable with the same name hides them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    /// `<Int>:Float`
+    ToFloat,
+    /// `<Float>:Int`, rounds towards zero.
+    Trunc,
+    /// `<Float>:Int`, rounds half away from zero.
+    Round,
+    /// `<String>:Int`, number of characters.
+    Len,
+    /// `<String,Int,Int>:String`, characters from start to end, excluding end.
+    Substring,
+    /// `<String,String>:Bool`
+    Contains,
+    /// `<String,String>:(String,String)`, parts before and after the first separator.
+    Split,
+    /// `<String>:String`
+    Trim,
+    /// `<String>:String`
+    ToUpper,
+    /// `<String>:String`
+    ToLower,
+    /// `<String>:(Bool,Int)`, the first item tells if the string was a valid integer.
+    ParseInt,
+    /// `<String>:(Bool,Float)`, the first item tells if the string was a valid float.
+    ParseFloat,
+}
+
+impl Builtin {
+    pub const ALL: [Builtin; 12] = [
+        Builtin::ToFloat, Builtin::Trunc, Builtin::Round,
+        Builtin::Len, Builtin::Substring, Builtin::Contains, Builtin::Split, Builtin::Trim,
+        Builtin::ToUpper, Builtin::ToLower, Builtin::ParseInt, Builtin::ParseFloat,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Builtin::ToFloat => "to_float",
+            Builtin::Trunc => "trunc",
+            Builtin::Round => "round",
+            Builtin::Len => "len",
+            Builtin::Substring => "substring",
+            Builtin::Contains => "contains",
+            Builtin::Split => "split",
+            Builtin::Trim => "trim",
+            Builtin::ToUpper => "to_upper",
+            Builtin::ToLower => "to_lower",
+            Builtin::ParseInt => "parse_int",
+            Builtin::ParseFloat => "parse_float",
+        }
+    }
+
+    pub fn find(name: &str) -> Option<Builtin> {
+        Self::ALL.into_iter().find(|b| b.name() == name)
+    }
+
+    pub fn func_type(&self) -> FuncType {
+        let t = |name: &str| TypeInfo::Struct(name.to_string());
+        let (params, ret) = match self {
+            Builtin::ToFloat => (vec![t(INT_TYPE)], t(FLOAT_TYPE)),
+            Builtin::Trunc | Builtin::Round => (vec![t(FLOAT_TYPE)], t(INT_TYPE)),
+            Builtin::Len => (vec![t(STRING_TYPE)], t(INT_TYPE)),
+            Builtin::Substring => (vec![t(STRING_TYPE), t(INT_TYPE), t(INT_TYPE)], t(STRING_TYPE)),
+            Builtin::Contains => (vec![t(STRING_TYPE), t(STRING_TYPE)], t(BOOL_TYPE)),
+            Builtin::Split => (vec![t(STRING_TYPE), t(STRING_TYPE)], TypeInfo::Tuple(vec![t(STRING_TYPE), t(STRING_TYPE)])),
+            Builtin::Trim | Builtin::ToUpper | Builtin::ToLower => (vec![t(STRING_TYPE)], t(STRING_TYPE)),
+            Builtin::ParseInt => (vec![t(STRING_TYPE)], TypeInfo::Tuple(vec![t(BOOL_TYPE), t(INT_TYPE)])),
+            Builtin::ParseFloat => (vec![t(STRING_TYPE)], TypeInfo::Tuple(vec![t(BOOL_TYPE), t(FLOAT_TYPE)])),
+        };
+        FuncType {
+            params: params.into_iter().map(|info| VariableType { mutable: false, info }).collect(),
+            return_type: Box::new(VariableType { mutable: false, info: ret }),
+        }
+    }
+
+    pub fn call<S: StdMod>(&self, params: &[DataObj<S>]) -> Result<DataObj<S>, RuntimeError> {
+        match (self, params) {
+            (Builtin::ToFloat, [DataObj::Int(i)]) => Ok(S::float_create(i.to_float())),
+            (Builtin::Trunc, [DataObj::Float(f)]) => Ok(S::int_create(f.trunc())),
+            (Builtin::Round, [DataObj::Float(f)]) => Ok(S::int_create(f.round())),
+            (Builtin::Len, [DataObj::String(s)]) => Ok(S::int_create(s.len())),
+            (Builtin::Substring, [DataObj::String(s), DataObj::Int(start), DataObj::Int(end)]) => {
+                let (start, end) = (start.to_i32(), end.to_i32());
+                s.substring(start, end).map(S::string_obj)
+                    .ok_or_else(|| RuntimeErrorKind::InvalidRange { start, end, len: s.len() }.into())
+            }
+            (Builtin::Contains, [DataObj::String(s), DataObj::String(other)]) => Ok(S::bool_obj(s.contains(other))),
+            (Builtin::Split, [DataObj::String(s), DataObj::String(separator)]) => {
+                let (first, second) = s.split(separator);
+                Ok(DataObj::Tuple(vec![S::string_obj(first), S::string_obj(second)]))
+            }
+            (Builtin::Trim, [DataObj::String(s)]) => Ok(S::string_obj(s.trim())),
+            (Builtin::ToUpper, [DataObj::String(s)]) => Ok(S::string_obj(s.to_upper())),
+            (Builtin::ToLower, [DataObj::String(s)]) => Ok(S::string_obj(s.to_lower())),
+            (Builtin::ParseInt, [DataObj::String(s)]) => {
+                let v = s.parse_int();
+                Ok(DataObj::Tuple(vec![S::bool_create(v.is_some()), S::int_create(v.unwrap_or_default())]))
+            }
+            (Builtin::ParseFloat, [DataObj::String(s)]) => {
+                let v = s.parse_float();
+                Ok(DataObj::Tuple(vec![S::bool_create(v.is_some()), S::float_create(v.unwrap_or_default())]))
+            }
+            _ => Err(RuntimeErrorKind::InvalidArguments {
+                function: self.name().to_string(),
+                found: params.iter().map(|p| p.type_str().to_string()).collect(),
+            }.into())
+        }
+    }
+}
@@ -1,6 +1,7 @@
-pub use basic_impl::StdModBasic;
-pub use traits::{BoolType, EmptyType, FloatType, FuncType, IntType, StdMod, StringType};
+pub use basic_impl::{StdModBasic, StdModSaturating, StdModWrapping};
+pub use traits::{BoolType, EmptyType, FloatType, FuncType, HostFn, IntType, StdMod, StringType};
 
 pub mod basic_impl;
+pub mod builtins;
 pub mod traits;
 
@@ -1,12 +1,19 @@
-use std::collections::HashMap;
+use std::rc::Rc;
 
 use crate::ast::expr::Expr;
+use crate::ast::states::Verified;
+use crate::ast::symbol::Symbol;
 use crate::run_env::{DataObj, RefDataObj};
+use crate::run_env::context::RunContext;
+use crate::run_env::error::RuntimeError;
+use crate::run_env::memo::{Memo, MemoCall};
+use crate::run_env::variable_stack::Globals;
+use crate::vm::{CallSite, Closure};
 
 pub trait StdMod: Sized + Clone {
     type INT: IntType<BOOL=Self::BOOL>;
     type FLOAT: FloatType<BOOL=Self::BOOL>;
-    type STRING: StringType;
+    type STRING: StringType<BOOL=Self::BOOL>;
     type FUNC: FuncType<S=Self>;
     type BOOL: BoolType;
     type EMPTY: EmptyType;
@@ -30,8 +37,8 @@ pub trait StdMod: Sized + Clone {
         DataObj::String(Self::STRING::create(txt))
     }
 
-    fn func_create(expr: Expr, params: Vec<String>, closure: HashMap<String, RefDataObj<Self>>) -> DataObj<Self> {
-        DataObj::Func(Self::FUNC::create(expr, params, closure))
+    fn func_create(expr: Rc<Expr<Verified>>, uses_self: bool, closure: Vec<RefDataObj<Self>>, globals: Globals<Self>, memo: Option<Rc<Memo<Self>>>) -> DataObj<Self> {
+        DataObj::Func(Self::FUNC::create(expr, uses_self, closure, globals, memo))
     }
     fn func_obj(f: Self::FUNC) -> DataObj<Self> {
         DataObj::Func(f)
@@ -45,18 +52,27 @@ pub trait StdMod: Sized + Clone {
         DataObj::Bool(b)
     }
 
+    fn string_obj(txt: Self::STRING) -> DataObj<Self> {
+        DataObj::String(txt)
+    }
+
     fn empty_create() -> DataObj<Self> {
         DataObj::Empty(Self::EMPTY::create())
     }
 }
 
+/// The overflow policy of the arithmetic is defined by the implementation.
+/// `None` means an overflow, which stops the script with a runtime error.
 pub trait IntType: Clone {
     type BOOL: BoolType;
 
     fn create(number: i32) -> Self;
-    fn plus(&self, other: &Self) -> Self;
-    fn minus(&self, other: &Self) -> Self;
+    fn plus(&self, other: &Self) -> Option<Self>;
+    fn minus(&self, other: &Self) -> Option<Self>;
     fn lt(&self, other: &Self) -> Self::BOOL;
+    fn eq(&self, other: &Self) -> Self::BOOL;
+    fn to_i32(&self) -> i32;
+    fn to_float(&self) -> f32;
 }
 
 pub trait FloatType: Clone {
@@ -66,16 +82,59 @@ pub trait FloatType: Clone {
     fn plus(&self, other: &Self) -> Self;
     fn minus(&self, other: &Self) -> Self;
     fn lt(&self, other: &Self) -> Self::BOOL;
+    fn eq(&self, other: &Self) -> Self::BOOL;
+    /// Rounds towards zero. Values out of the range of `i32` are saturated.
+    fn trunc(&self) -> i32;
+    /// Rounds half away from zero. Values out of the range of `i32` are saturated.
+    fn round(&self) -> i32;
+    fn to_f32(&self) -> f32;
 }
 
+/// Lengths and indices are counted in characters.
 pub trait StringType: Clone {
+    type BOOL: BoolType;
+
     fn create(txt: String) -> Self;
+    fn as_str(&self) -> &str;
+    fn concat(&self, other: &Self) -> Self;
+    fn lt(&self, other: &Self) -> Self::BOOL;
+    fn eq(&self, other: &Self) -> Self::BOOL;
+    fn len(&self) -> i32;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Characters from `start` to `end`, excluding `end`. `None` if the range is not inside the string.
+    fn substring(&self, start: i32, end: i32) -> Option<Self>;
+    fn contains(&self, other: &Self) -> Self::BOOL;
+    /// Splits at the first occurrence of `separator`. If there is none, the second part is empty.
+    fn split(&self, separator: &Self) -> (Self, Self);
+    fn trim(&self) -> Self;
+    fn to_upper(&self) -> Self;
+    fn to_lower(&self) -> Self;
+    fn parse_int(&self) -> Option<i32>;
+    fn parse_float(&self) -> Option<f32>;
 }
 
+/// Implementation of a function registered by the host. An error message stops the execution with a runtime error.
+pub type HostFn<S> = Rc<dyn Fn(&[DataObj<S>]) -> Result<DataObj<S>, String>>;
+
 pub trait FuncType: Clone {
     type S: StdMod;
-    fn create(expr: Expr, params: Vec<String>, closure: HashMap<String, RefDataObj<Self::S>>) -> Self;
-    fn call(&self, params: Vec<RefDataObj<Self::S>>) -> RefDataObj<Self::S>;
+    /// `uses_self` tells if the body refers to `self_fn`, which is then created for each call.
+    /// `closure` contains the captured variables in the order of their slots.
+    /// `memo` is the cache of the results, if the function is memoized.
+    fn create(expr: Rc<Expr<Verified>>, uses_self: bool, closure: Vec<RefDataObj<Self::S>>, globals: Globals<Self::S>, memo: Option<Rc<Memo<Self::S>>>) -> Self;
+    /// Creates a function of the bytecode backend.
+    fn compiled(closure: Closure<Self::S>) -> Self;
+    /// Creates a function implemented by the host.
+    fn host(name: Symbol, function: HostFn<Self::S>) -> Self;
+    /// The bytecode of a function created with `compiled`. The stack machine calls it without recursion on the native stack.
+    fn as_compiled(&self) -> Option<&Closure<Self::S>>;
+    /// Runs the function. `context` is the state of the execution calling it, the call is an evaluation step of it.
+    fn call(&self, params: Vec<RefDataObj<Self::S>>, context: Rc<RunContext>) -> Result<RefDataObj<Self::S>, RuntimeError>;
+    /// Runs the function as the tail call `site` of a chain of tail calls. `memos` are the calls of memoized functions
+    /// waiting for the result of the chain.
+    fn tail_call(&self, params: Vec<RefDataObj<Self::S>>, context: Rc<RunContext>, memos: Vec<MemoCall<Self::S>>, site: CallSite) -> Result<RefDataObj<Self::S>, RuntimeError>;
 }
 
 pub trait BoolType: Clone {
@@ -0,0 +1,159 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::expr::{Expr, FunctionDef, PartialExpr, VariableExpr};
+use crate::ast::states::AstState;
+use crate::ast::symbol::Symbol;
+use crate::ast::variable::VariableName;
+use crate::verify::error::{TypeError, TypeErrorKind};
+
+/// Groups the module items into strongly connected components of the "uses" relation.
+/// Components are returned in evaluation order: every item comes after the items it depends on.
+/// `self_fn` is the symbol of `self_fn` in the table of the variables.
+pub(crate) fn ordered_components<S: AstState>(variables: &[VariableName<S>], self_fn: &Symbol) -> Result<Vec<Vec<usize>>, TypeError> {
+    let mut indices = HashMap::new();
+    for (i, v) in variables.iter().enumerate() {
+        if indices.insert(v.name.clone(), i).is_some() {
+            return Err(TypeError::from(TypeErrorKind::DuplicateVariable { name: v.name.to_string() }).at(v.location));
+        }
+    }
+
+    let edges: Vec<Vec<usize>> = variables.iter().map(|v| {
+        let mut names = HashSet::new();
+        v.variable.value.collect_free_names(self_fn, &mut Vec::new(), &mut names);
+        let mut deps: Vec<usize> = names.iter().filter_map(|n| indices.get(n).copied()).collect();
+        deps.sort_unstable();
+        deps
+    }).collect();
+
+    let mut tarjan = Tarjan::new(&edges);
+    for i in 0..variables.len() {
+        if tarjan.index[i].is_none() {
+            tarjan.visit(i);
+        }
+    }
+
+    for component in &tarjan.components {
+        let recursive = component.len() > 1 || edges[component[0]].contains(&component[0]);
+        if recursive {
+            if let Some(&i) = component.iter().find(|&&i| variables[i].variable.value.as_lambda().is_none()) {
+                return Err(TypeError::from(TypeErrorKind::RecursiveValue { name: variables[i].name.to_string() }).at(variables[i].location));
+            }
+        }
+    }
+    Ok(tarjan.components)
+}
+
+struct Tarjan<'a> {
+    edges: &'a [Vec<usize>],
+    counter: usize,
+    index: Vec<Option<usize>>,
+    low_link: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<usize>,
+    components: Vec<Vec<usize>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn new(edges: &'a [Vec<usize>]) -> Self {
+        let n = edges.len();
+        Tarjan { edges, counter: 0, index: vec![None; n], low_link: vec![0; n], on_stack: vec![false; n], stack: Vec::new(), components: Vec::new() }
+    }
+
+    fn visit(&mut self, v: usize) {
+        self.index[v] = Some(self.counter);
+        self.low_link[v] = self.counter;
+        self.counter += 1;
+        self.stack.push(v);
+        self.on_stack[v] = true;
+
+        for &w in &self.edges[v] {
+            match self.index[w] {
+                None => {
+                    self.visit(w);
+                    self.low_link[v] = self.low_link[v].min(self.low_link[w]);
+                }
+                Some(i) if self.on_stack[w] => self.low_link[v] = self.low_link[v].min(i),
+                _ => ()
+            }
+        }
+
+        if Some(self.low_link[v]) == self.index[v] {
+            let mut component = Vec::new();
+            while let Some(w) = self.stack.pop() {
+                self.on_stack[w] = false;
+                component.push(w);
+                if w == v { break; }
+            }
+            component.sort_unstable();
+            self.components.push(component);
+        }
+    }
+}
+
+impl<S: AstState> Expr<S> {
+    pub(crate) fn as_lambda(&self) -> Option<&FunctionDef<S>> {
+        match self.exprs.as_slice() {
+            [PartialExpr::Lambda(fd)] => Some(fd),
+            _ => None
+        }
+    }
+
+    pub(crate) fn as_lambda_mut(&mut self) -> Option<&mut FunctionDef<S>> {
+        match self.exprs.as_mut_slice() {
+            [PartialExpr::Lambda(fd)] => Some(fd),
+            _ => None
+        }
+    }
+
+    /// Collects the names used in the expression that are not bound inside of it.
+    fn collect_free_names(&self, self_fn: &Symbol, bound: &mut Vec<Symbol>, names: &mut HashSet<Symbol>) {
+        for e in &self.exprs {
+            e.collect_free_names(self_fn, bound, names)
+        }
+    }
+}
+
+impl<S: AstState> PartialExpr<S> {
+    fn collect_free_names(&self, self_fn: &Symbol, bound: &mut Vec<Symbol>, names: &mut HashSet<Symbol>) {
+        match self {
+            PartialExpr::Block(_) => (),
+            PartialExpr::If(e) => {
+                e.cond_expr.collect_free_names(self_fn, bound, names);
+                e.main_branch.collect_free_names(self_fn, bound, names);
+                if let Some(eb) = &e.else_branch {
+                    eb.collect_free_names(self_fn, bound, names)
+                }
+            }
+            PartialExpr::FunctionCall(e) => {
+                add_name(&e.name, bound, names);
+                for p in &e.params {
+                    p.collect_free_names(self_fn, bound, names)
+                }
+            }
+            PartialExpr::Variable(VariableExpr::Variable { name, .. }) => add_name(name, bound, names),
+            PartialExpr::Variable(VariableExpr::Constant(_)) => (),
+            PartialExpr::Lambda(fd) => {
+                for c in &fd.closure {
+                    add_name(&c.name, bound, names)
+                }
+                let len = bound.len();
+                bound.extend(fd.parameters.iter().map(|(name, _)| name.clone()));
+                bound.extend(fd.closure.iter().map(|c| c.name.clone()));
+                bound.push(self_fn.clone());
+                fd.expr.collect_free_names(self_fn, bound, names);
+                bound.truncate(len);
+            }
+            PartialExpr::Tuple(t) => {
+                for e in &t.items {
+                    e.collect_free_names(self_fn, bound, names)
+                }
+            }
+        }
+    }
+}
+
+fn add_name(name: &Symbol, bound: &[Symbol], names: &mut HashSet<Symbol>) {
+    if !bound.contains(name) {
+        names.insert(name.clone());
+    }
+}
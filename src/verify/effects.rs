@@ -0,0 +1,116 @@
+use std::rc::Rc;
+
+use crate::ast::Module;
+use crate::ast::effects::Effects;
+use crate::ast::expr::{Expr, FunctionCallExpr, FunctionDef, PartialExpr};
+use crate::ast::states::Verified;
+use crate::ast::variable::{Binding, VariableName};
+use crate::verify::SELF_FN;
+use crate::verify::error::{TypeError, TypeErrorKind};
+use crate::verify::variable_mng::VariableManager;
+
+impl Module<Verified> {
+    /// Effects of calling the variable, if it is defined by a lambda.
+    pub fn effects(&self, name: &str) -> Option<Effects> {
+        let name = self.symbols.find(name)?;
+        let v = self.variables.iter().find(|v| v.name == name)?;
+        Some(v.variable.value.as_lambda()?.effects)
+    }
+}
+
+/// Infers the effects of the variables of a component. The functions of a component can call each other,
+/// so their effects are assumed to be pure, and grow until they do not change.
+pub(crate) fn infer_component_effects(members: &mut [VariableName<Verified>], variable_mng: &mut VariableManager) {
+    for v in members.iter().filter(|v| v.variable.value.as_lambda().is_some()) {
+        variable_mng.add_effects(v.name.clone(), Effects::PURE)
+    }
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for v in members.iter_mut() {
+            v.variable.value.infer_effects(variable_mng);
+            if let Some(fd) = v.variable.value.as_lambda() {
+                if variable_mng.effects(&v.name) != Some(fd.effects) {
+                    variable_mng.add_effects(v.name.clone(), fd.effects);
+                    changed = true;
+                }
+            }
+        }
+    }
+}
+
+impl Expr<Verified> {
+    /// Infers the effects of the lambdas in the expression. Returns the effects of evaluating the expression.
+    pub(crate) fn infer_effects(&mut self, variable_mng: &VariableManager) -> Effects {
+        self.exprs.iter_mut().map(|e| e.infer_effects(variable_mng)).fold(Effects::PURE, |a, b| a | b)
+    }
+
+    /// Checks that the functions annotated `pure` have no effects.
+    pub(crate) fn check_pure(&self) -> Result<(), TypeError> {
+        self.exprs.iter().try_for_each(|e| e.check_pure())
+    }
+}
+
+impl PartialExpr<Verified> {
+    fn infer_effects(&mut self, variable_mng: &VariableManager) -> Effects {
+        match self {
+            PartialExpr::Block(_) | PartialExpr::Variable(_) => Effects::PURE,
+            PartialExpr::If(e) => {
+                let effects = e.cond_expr.infer_effects(variable_mng) | e.main_branch.infer_effects(variable_mng);
+                match &mut e.else_branch {
+                    Some(eb) => effects | eb.infer_effects(variable_mng),
+                    None => effects
+                }
+            }
+            PartialExpr::FunctionCall(e) => {
+                let callee = e.callee_effects(variable_mng);
+                e.params.iter_mut().map(|p| p.infer_effects(variable_mng)).fold(callee, |a, b| a | b)
+            }
+            // Creating a function value has no effects, calling it has.
+            PartialExpr::Lambda(fd) => {
+                fd.infer_effects(variable_mng);
+                Effects::PURE
+            }
+            PartialExpr::Tuple(t) => t.items.iter_mut().map(|i| i.infer_effects(variable_mng)).fold(Effects::PURE, |a, b| a | b),
+        }
+    }
+
+    fn check_pure(&self) -> Result<(), TypeError> {
+        match self {
+            PartialExpr::Block(_) | PartialExpr::Variable(_) => Ok(()),
+            PartialExpr::If(e) => {
+                e.cond_expr.check_pure()?;
+                e.main_branch.check_pure()?;
+                e.else_branch.iter().try_for_each(|eb| eb.check_pure())
+            }
+            PartialExpr::FunctionCall(e) => e.params.iter().try_for_each(|p| p.check_pure()),
+            PartialExpr::Lambda(fd) => {
+                if fd.pure && !fd.effects.is_pure() {
+                    return Err(TypeError::from(TypeErrorKind::NotPure { effects: fd.effects }).at(fd.location));
+                }
+                fd.expr.check_pure()
+            }
+            PartialExpr::Tuple(t) => t.items.iter().try_for_each(|i| i.check_pure()),
+        }
+    }
+}
+
+impl FunctionCallExpr<Verified> {
+    fn callee_effects(&self, variable_mng: &VariableManager) -> Effects {
+        match self.binding {
+            Binding::Builtin(_) => Effects::PURE,
+            Binding::Host { .. } => Effects::HOST_CALL,
+            Binding::Global { .. } => variable_mng.effects(&self.name).unwrap_or(Effects::UNKNOWN_CALL),
+            // A recursive call has the effects of the function itself.
+            Binding::Local { .. } if *self.name.name() == *SELF_FN => Effects::PURE,
+            Binding::Local { .. } => Effects::UNKNOWN_CALL
+        }
+    }
+}
+
+impl FunctionDef<Verified> {
+    fn infer_effects(&mut self, variable_mng: &VariableManager) {
+        let captures = Effects { mutation: self.closure.iter().any(|c| c.mutable), ..Effects::PURE };
+        self.effects = captures | Rc::make_mut(&mut self.expr).infer_effects(variable_mng);
+    }
+}
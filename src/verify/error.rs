@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use crate::ast::effects::Effects;
+use crate::ast::expr::Operand;
+use crate::ast::location::Location;
+use crate::ast::types::{TypeInfo, VariableType};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeErrorKind {
+    UnknownVariable { name: String },
+    UnknownModule { module: String },
+    PrivateVariable { module: String, name: String },
+    DuplicateVariable { name: String },
+    /// A value, which is not a function, depends on itself.
+    RecursiveValue { name: String },
+    /// An imported variable is also defined in the module.
+    ImportConflict { name: String },
+    Mismatch { expected: TypeInfo, found: TypeInfo },
+    InfiniteType { expected: TypeInfo, found: TypeInfo },
+    IncorrectMutability { expected: VariableType, found: VariableType },
+    OperandNotDefined { lhs: TypeInfo, operand: Operand, rhs: TypeInfo },
+    ConditionNotBool { found: VariableType },
+    IfWithoutElse { found: VariableType },
+    BranchMismatch { main: VariableType, other: VariableType },
+    NotFunction { name: String, found: TypeInfo },
+    WrongArgumentCount { expected: usize, found: usize },
+    NotMutable { name: String },
+    Unimplemented { feature: String },
+    /// Functions have no equality.
+    FunctionComparison { found: TypeInfo },
+    /// A memoized function has a mutable parameter or captured variable.
+    MutableMemo { name: String },
+    /// A function annotated `pure` has effects.
+    NotPure { effects: Effects },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeError {
+    pub kind: TypeErrorKind,
+    /// Location of the innermost expression containing the error, if known.
+    pub location: Option<Location>,
+}
+
+impl TypeError {
+    /// Numbers the type variables of the error from zero in order of appearance, like in the verified types,
+    /// so that the variables of the type inference are displayed as `'a`, `'b`, ...
+    pub(crate) fn renumbered(mut self) -> Self {
+        let mut ids = HashMap::new();
+        let mut renumber = |id: usize| {
+            let next = ids.len();
+            TypeInfo::Var(*ids.entry(id).or_insert(next))
+        };
+        match &mut self.kind {
+            TypeErrorKind::Mismatch { expected, found } | TypeErrorKind::InfiniteType { expected, found }
+            | TypeErrorKind::OperandNotDefined { lhs: expected, rhs: found, .. } => {
+                *expected = expected.substitute(&mut renumber);
+                *found = found.substitute(&mut renumber);
+            }
+            TypeErrorKind::IncorrectMutability { expected, found } | TypeErrorKind::BranchMismatch { main: expected, other: found } => {
+                *expected = expected.substitute(&mut renumber);
+                *found = found.substitute(&mut renumber);
+            }
+            TypeErrorKind::ConditionNotBool { found } | TypeErrorKind::IfWithoutElse { found } => *found = found.substitute(&mut renumber),
+            TypeErrorKind::NotFunction { found, .. } | TypeErrorKind::FunctionComparison { found } => *found = found.substitute(&mut renumber),
+            _ => ()
+        }
+        self
+    }
+
+    /// Stable code of the error kind.
+    pub fn code(&self) -> &'static str {
+        match self.kind {
+            TypeErrorKind::UnknownVariable { .. } => "T0001",
+            TypeErrorKind::UnknownModule { .. } => "T0002",
+            TypeErrorKind::PrivateVariable { .. } => "T0003",
+            TypeErrorKind::DuplicateVariable { .. } => "T0004",
+            TypeErrorKind::RecursiveValue { .. } => "T0005",
+            TypeErrorKind::ImportConflict { .. } => "T0006",
+            TypeErrorKind::Mismatch { .. } => "T0007",
+            TypeErrorKind::InfiniteType { .. } => "T0008",
+            TypeErrorKind::IncorrectMutability { .. } => "T0009",
+            TypeErrorKind::OperandNotDefined { .. } => "T0010",
+            TypeErrorKind::ConditionNotBool { .. } => "T0011",
+            TypeErrorKind::IfWithoutElse { .. } => "T0012",
+            TypeErrorKind::BranchMismatch { .. } => "T0013",
+            TypeErrorKind::NotFunction { .. } => "T0014",
+            TypeErrorKind::WrongArgumentCount { .. } => "T0015",
+            TypeErrorKind::NotMutable { .. } => "T0016",
+            TypeErrorKind::Unimplemented { .. } => "T0017",
+            TypeErrorKind::FunctionComparison { .. } => "T0018",
+            TypeErrorKind::MutableMemo { .. } => "T0019",
+            TypeErrorKind::NotPure { .. } => "T0020",
+        }
+    }
+
+    /// Sets the location, unless a more precise one is already known.
+    pub(crate) fn at(mut self, location: Location) -> Self {
+        self.location.get_or_insert(location);
+        self
+    }
+}
+
+impl From<TypeErrorKind> for TypeError {
+    fn from(kind: TypeErrorKind) -> Self {
+        TypeError { kind, location: None }
+    }
+}
+
+impl Display for TypeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] ", self.code())?;
+        match &self.kind {
+            TypeErrorKind::UnknownVariable { name } => write!(f, "Cannot find variable: {}", name),
+            TypeErrorKind::UnknownModule { module } => write!(f, "Cannot find module: {}", module),
+            TypeErrorKind::PrivateVariable { module, name } => write!(f, "Variable {} in module {} is private.", name, module),
+            TypeErrorKind::DuplicateVariable { name } => write!(f, "Variable {} is defined more than once.", name),
+            TypeErrorKind::RecursiveValue { name } => write!(f, "Variable {} is defined recursively. Only functions can be recursive.", name),
+            TypeErrorKind::ImportConflict { name } => write!(f, "Imported variable {} is also defined in the module.", name),
+            TypeErrorKind::Mismatch { expected, found } => write!(f, "Wrong type. expected:{}, found:{}", expected, found),
+            TypeErrorKind::InfiniteType { expected, found } => write!(f, "Infinite type. expected:{}, found:{}", expected, found),
+            TypeErrorKind::IncorrectMutability { expected, found } => write!(f, "Incorrect mutability. expected:{}, found:{}", expected, found),
+            TypeErrorKind::OperandNotDefined { lhs, operand, rhs } => write!(f, "not defined: {} {:?} {}", lhs, operand, rhs),
+            TypeErrorKind::ConditionNotBool { found } => write!(f, "Conditional expression must return Bool. found:{}", found),
+            TypeErrorKind::IfWithoutElse { found } => write!(f, "If without else, must return empty. found:{}", found),
+            TypeErrorKind::BranchMismatch { main, other } => write!(f, "Both if branches must return same type. found: main:{}, else:{}", main, other),
+            TypeErrorKind::NotFunction { name, found } => write!(f, "Variable {} is not function: found type:{}", name, found),
+            TypeErrorKind::WrongArgumentCount { expected, found } => write!(f, "Wrong number of params. expected:{}, found:{}", expected, found),
+            TypeErrorKind::NotMutable { name } => write!(f, "Variable {} is not mutable.", name),
+            TypeErrorKind::Unimplemented { feature } => write!(f, "Unimplemented: {}", feature),
+            TypeErrorKind::FunctionComparison { found } => write!(f, "Functions can not be compared for equality. found:{}", found),
+            TypeErrorKind::MutableMemo { name } => write!(f, "Memoized function can not have mutable parameter or capture: {}", name),
+            TypeErrorKind::NotPure { effects } => write!(f, "Function annotated pure has effects: {}", effects),
+        }?;
+        match &self.location {
+            Some(l) => write!(f, " at {}", l),
+            None => Ok(())
+        }
+    }
+}
+
+impl Error for TypeError {}
@@ -1,165 +1,413 @@
-use crate::ast::Module;
-use crate::ast::expr::{BlockExpr, Expr, FunctionCallExpr, FunctionDef, IfExpr, Operand, PartialExpr, TupleDef, VariableExpr};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::ast::{Module, VariableDef};
+use crate::ast::expr::{BlockExpr, Capture, Expr, FunctionCallExpr, FunctionDef, IfExpr, Operand, PartialExpr, TupleDef, VariableExpr};
 use crate::ast::states::{Unverified, Verified};
+use crate::ast::symbol::Symbol;
 use crate::ast::types::{FuncType, TypeInfo, VariableType};
-use crate::ast::variable::{ConstantValue, VariableName};
+use crate::ast::location::Location;
+use crate::ast::variable::{qualified_name, Binding, ConstantValue, Link, VariableName};
+use crate::ast::effects::Effects;
+use crate::verify::dependencies::ordered_components;
+use crate::verify::effects::infer_component_effects;
+use crate::verify::error::{TypeError, TypeErrorKind};
 use crate::verify::variable_mng::VariableManager;
 
+mod dependencies;
+mod effects;
+pub mod error;
 pub(crate) mod variable_mng;
 
-const INT_TYPE: &'static str = "Int";
-const FLOAT_TYPE: &'static str = "Float";
-const STRING_TYPE: &'static str = "String";
-const BOOL_TYPE: &'static str = "Bool";
+pub(crate) const INT_TYPE: &str = "Int";
+pub(crate) const FLOAT_TYPE: &str = "Float";
+pub(crate) const STRING_TYPE: &str = "String";
+pub(crate) const BOOL_TYPE: &str = "Bool";
+pub(crate) const SELF_FN: &str = "self_fn";
 
 impl Module<Unverified> {
-    pub fn verify(mut self) -> Result<Module<Verified>, String> {
-        let mut variable_mng = VariableManager::new();
-        for VariableName { name, variable } in &mut self.variables {
-            let checked_type = variable.value.check_type(&mut variable_mng)?.check_expected(&variable.v_type)?;
-            //println!("{}: {}, {}",name,variable.v_type,checked_type);
+    /// Checks the types of the module. Variables are verified and stored in dependency order,
+    /// so that functions can refer to each other and to themselves by name.
+    /// The effects of every function are inferred, and the functions annotated `pure` must have none.
+    /// `modules` are the already verified modules, which can be imported or referred with qualified names.
+    /// They must use the symbol table of the module.
+    pub fn verify(self, modules: &HashMap<String, Module<Verified>>) -> Result<Module<Verified>, TypeError> {
+        let variable_mng = VariableManager::new(self.symbols.clone());
+        self.verify_with(modules, variable_mng)
+    }
+
+    /// Verifies the module with a manager, which can already contain the functions registered by the host.
+    pub(crate) fn verify_with(self, modules: &HashMap<String, Module<Verified>>, variable_mng: VariableManager) -> Result<Module<Verified>, TypeError> {
+        self.check_module(modules, variable_mng).map_err(TypeError::renumbered)
+    }
+
+    fn check_module(self, modules: &HashMap<String, Module<Verified>>, mut variable_mng: VariableManager) -> Result<Module<Verified>, TypeError> {
+        let components = ordered_components(&self.variables, &self.symbols.intern(SELF_FN))?;
+        variable_mng.declare_globals(components.iter().flatten().map(|&i| self.variables[i].name.clone()));
+        for (name, m) in modules {
+            variable_mng.add_module(name, m);
+        }
+        for import in &self.imports {
+            let location = import.location;
+            let m = modules.get(&*import.module.name())
+                .ok_or_else(|| TypeError::from(TypeErrorKind::UnknownModule { module: import.module.to_string() }).at(location))?;
+            for name in &import.names {
+                if let Some(v) = self.variables.iter().find(|v| &v.name == name) {
+                    return Err(TypeError::from(TypeErrorKind::ImportConflict { name: name.to_string() }).at(v.location));
+                }
+                let v = m.variables.iter().find(|v| &v.name == name)
+                    .ok_or_else(|| TypeError::from(TypeErrorKind::UnknownVariable { name: qualified_name(&import.module.name(), &name.name()) }).at(location))?;
+                if !v.public {
+                    return Err(TypeError::from(TypeErrorKind::PrivateVariable { module: import.module.to_string(), name: name.to_string() }).at(location));
+                }
+                let link = Link::Module { module: import.module.clone(), name: name.clone() };
+                variable_mng.add_generic(name.clone(), v.variable.v_type.clone(), v.variable.comparable.clone(), link);
+                if let Some(fd) = v.variable.value.as_lambda() {
+                    variable_mng.add_effects(name.clone(), fd.effects)
+                }
+            }
+        }
+        let mut variables: Vec<Option<VariableName<Unverified>>> = self.variables.into_iter().map(Some).collect();
+        let mut ordered = Vec::with_capacity(variables.len());
 
-            variable_mng.add_variable(name.to_string(), checked_type.clone());
-            variable.v_type = checked_type;
+        for component in components {
+            let mut members: Vec<VariableName<Unverified>> = component.iter().map(|&i| variables[i].take().unwrap()).collect();
+            for VariableName { name, location, variable, .. } in &mut members {
+                if let Some(fd) = variable.value.as_lambda_mut() {
+                    fd.fill_unknown_types(&mut variable_mng);
+                    let declared = VariableType { mutable: true, info: TypeInfo::Function((&*fd).into()) };
+                    let declared = variable_mng.expect(declared, &variable.v_type).map_err(|e| e.at(*location))?;
+                    variable_mng.add_variable(name.clone(), declared);
+                }
+            }
+            let mut checked = Vec::with_capacity(members.len());
+            for VariableName { name, location, public, variable } in members {
+                let value = variable.value.check_type(&mut variable_mng)?;
+                let checked_type = variable_mng.expect(value.v_type.clone(), &variable.v_type).map_err(|e| e.at(location))?;
+                variable_mng.add_variable(name.clone(), checked_type.clone());
+                checked.push(VariableName { name, location, public, variable: VariableDef { value, v_type: checked_type, comparable: Vec::new() } });
+            }
+            variable_mng.check_operands()?;
+            infer_component_effects(&mut checked, &mut variable_mng);
+            for mut v in checked {
+                (v.variable.v_type, v.variable.comparable) = variable_mng.generalize(&v.name);
+                ordered.push(v);
+            }
+        }
+
+        for VariableName { variable, .. } in &mut ordered {
+            variable.value.check_pure()?;
+            let mut ids = HashMap::new();
+            variable.v_type = resolve_and_renumber(&variable.v_type, &variable_mng, &mut ids);
+            variable.comparable = variable.comparable.iter().map(|id| ids[id]).collect();
+            variable.value.map_types(&mut |vt| resolve_and_renumber(vt, &variable_mng, &mut ids));
         }
-        Ok(Module { variables: self.variables, structs: self.structs, _state: Default::default() })
+        Ok(Module { imports: self.imports, variables: ordered, structs: self.structs, links: variable_mng.links(), symbols: self.symbols, _state: Default::default() })
     }
 }
 
-impl Expr {
-    pub(crate) fn check_type(&self, variable_mng: &mut VariableManager) -> Result<VariableType, String> {
-        let mut expr_iter = self.exprs.iter();
-        let first_expr = expr_iter.next().unwrap();
-        let mut type_name = first_expr.check_type(variable_mng)?;
+impl Expr<Unverified> {
+    pub(crate) fn check_type(self, variable_mng: &mut VariableManager) -> Result<Expr<Verified>, TypeError> {
+        let location = self.location;
+        let mut expr_iter = self.exprs.into_iter();
+        let first_expr = expr_iter.next().unwrap().check_type(variable_mng).map_err(|e| e.at(location))?;
+        let mut type_name = first_expr.v_type();
+        let mut exprs = vec![first_expr];
 
         for (e, o) in expr_iter.zip(&self.operands) {
-            type_name = check_valid_operand(&type_name, o, &e.check_type(variable_mng)?)?
+            let e = e.check_type(variable_mng).map_err(|e| e.at(location))?;
+            type_name = check_valid_operand(&type_name, *o, &e.v_type(), variable_mng, location)?;
+            exprs.push(e);
         }
 
-        Ok(type_name)
+        Ok(Expr { exprs, operands: self.operands, location, v_type: type_name })
     }
 }
 
-fn check_valid_operand(lhs: &VariableType, op: &Operand, rhs: &VariableType) -> Result<VariableType, String> {
-    match (&lhs.info, op, &rhs.info) {
-        (TypeInfo::Struct(l), Operand::Plus, TypeInfo::Struct(r)) if l == r && (l == INT_TYPE || l == FLOAT_TYPE) => Ok(VariableType { mutable: true, info: lhs.info.clone() }),
-        (TypeInfo::Struct(l), Operand::Minus, TypeInfo::Struct(r)) if l == r && (l == INT_TYPE || l == FLOAT_TYPE) => Ok(VariableType { mutable: true, info: lhs.info.clone() }),
-        (TypeInfo::Struct(l), Operand::Lt, TypeInfo::Struct(r)) if l == r && (l == INT_TYPE || l == FLOAT_TYPE) => Ok(VariableType { mutable: true, info: TypeInfo::Struct(BOOL_TYPE.to_string()) }),
-        _ => Result::Err(format!("not defined: {} {:?} {}", lhs, op, rhs))
+impl Expr<Verified> {
+    /// Replaces every type stored in the expression.
+    pub(crate) fn map_types(&mut self, f: &mut impl FnMut(&VariableType) -> VariableType) {
+        self.v_type = f(&self.v_type);
+        for e in &mut self.exprs {
+            match e {
+                PartialExpr::Block(_) | PartialExpr::Variable(VariableExpr::Constant(_)) => (),
+                PartialExpr::Variable(VariableExpr::Variable { v_type, .. }) => *v_type = f(v_type),
+                PartialExpr::If(e) => {
+                    e.v_type = f(&e.v_type);
+                    e.cond_expr.map_types(f);
+                    e.main_branch.map_types(f);
+                    if let Some(eb) = &mut e.else_branch {
+                        eb.map_types(f)
+                    }
+                }
+                PartialExpr::FunctionCall(e) => {
+                    e.v_type = f(&e.v_type);
+                    e.params.iter_mut().for_each(|p| p.map_types(f))
+                }
+                PartialExpr::Lambda(fd) => {
+                    for (_, vt) in &mut fd.parameters {
+                        *vt = f(vt)
+                    }
+                    fd.return_type = f(&fd.return_type);
+                    Rc::make_mut(&mut fd.expr).map_types(f)
+                }
+                PartialExpr::Tuple(t) => {
+                    t.v_type = f(&t.v_type);
+                    t.items.iter_mut().for_each(|i| i.map_types(f))
+                }
+            }
+        }
     }
-    //TODO: check operands for local types
+}
 
-    //Err(format!("not defined: {} {:?} {}", lhs, op, rhs))
+impl Expr<Verified> {
+    /// Marks the calls, which are the last evaluated expression, when this is the body of a function.
+    fn mark_tail_calls(&mut self) {
+        if !self.operands.is_empty() {
+            return;
+        }
+        match self.exprs.as_mut_slice() {
+            [PartialExpr::FunctionCall(call)] => call.tail = !matches!(call.binding, Binding::Builtin(_)),
+            [PartialExpr::If(e)] => {
+                e.main_branch.mark_tail_calls();
+                if let Some(eb) = &mut e.else_branch {
+                    eb.mark_tail_calls()
+                }
+            }
+            _ => ()
+        }
+    }
 }
 
-impl PartialExpr {
-    fn check_type(&self, variable_mng: &mut VariableManager) -> Result<VariableType, String> {
+impl PartialExpr<Verified> {
+    pub fn v_type(&self) -> VariableType {
         match self {
-            PartialExpr::Block(e) => e.check_type(variable_mng),
-            PartialExpr::If(e) => e.check_type(variable_mng),
-            PartialExpr::Variable(e) => e.check_type(variable_mng),
-            PartialExpr::FunctionCall(e) => e.check_type(variable_mng),
-            PartialExpr::Lambda(e) => e.check_type(variable_mng),
-            PartialExpr::Tuple(e) => e.check_type(variable_mng)
+            PartialExpr::Block(_) => VariableType { mutable: false, info: TypeInfo::empty() },
+            PartialExpr::If(e) => e.v_type.clone(),
+            PartialExpr::FunctionCall(e) => e.v_type.clone(),
+            PartialExpr::Variable(VariableExpr::Variable { v_type, .. }) => v_type.clone(),
+            PartialExpr::Variable(VariableExpr::Constant(cv)) => VariableType { mutable: true, info: get_type(cv) },
+            PartialExpr::Lambda(fd) => VariableType { mutable: true, info: TypeInfo::Function(fd.into()) },
+            PartialExpr::Tuple(t) => t.v_type.clone(),
         }
     }
 }
 
+fn check_valid_operand(lhs: &VariableType, op: Operand, rhs: &VariableType, variable_mng: &mut VariableManager, location: Location) -> Result<VariableType, TypeError> {
+    let not_defined = |variable_mng: &VariableManager| TypeError::from(TypeErrorKind::OperandNotDefined {
+        lhs: variable_mng.resolve(&lhs.info),
+        operand: op,
+        rhs: variable_mng.resolve(&rhs.info),
+    }).at(location);
+    if variable_mng.unify(&lhs.info, &rhs.info).is_err() {
+        return Err(not_defined(variable_mng));
+    }
+    variable_mng.require_operand(lhs.info.clone(), op, location)?;
+    //TODO: check operands for local types
+    let info = match op {
+        Operand::Plus | Operand::Minus => lhs.info.clone(),
+        Operand::Lt | Operand::Eq | Operand::Ne => TypeInfo::Struct(BOOL_TYPE.to_string()),
+    };
+    Ok(VariableType { mutable: true, info })
+}
+
+/// Type variables of a verified variable are numbered from zero, so they are displayed as `'a`, `'b`, ...
+fn resolve_and_renumber(vt: &VariableType, variable_mng: &VariableManager, ids: &mut HashMap<usize, usize>) -> VariableType {
+    variable_mng.resolve_variable(vt).substitute(&mut |id| {
+        let next = ids.len();
+        TypeInfo::Var(*ids.entry(id).or_insert(next))
+    })
+}
+
+/// Checks that the operand is defined for two values of the type. Type variables are not checked.
+/// Equality is structural and defined for every type except functions. Tuples are ordered lexicographically.
+fn check_defined(op: Operand, info: &TypeInfo) -> Result<(), TypeErrorKind> {
+    let not_defined = || TypeErrorKind::OperandNotDefined { lhs: info.clone(), operand: op, rhs: info.clone() };
+    match (op, info) {
+        (_, TypeInfo::Var(_)) => Ok(()),
+        (Operand::Eq | Operand::Ne, TypeInfo::Function(_)) => Err(TypeErrorKind::FunctionComparison { found: info.clone() }),
+        (Operand::Eq | Operand::Ne, TypeInfo::Struct(_)) => Ok(()),
+        (Operand::Eq | Operand::Ne | Operand::Lt, TypeInfo::Tuple(items)) => items.iter().try_for_each(|i| check_defined(op, i)),
+        (Operand::Lt, TypeInfo::Struct(name)) if name == BOOL_TYPE || name == STRING_TYPE || is_numeric(name) => Ok(()),
+        (Operand::Plus, TypeInfo::Struct(name)) if name == STRING_TYPE || is_numeric(name) => Ok(()),
+        (Operand::Minus, TypeInfo::Struct(name)) if is_numeric(name) => Ok(()),
+        _ => Err(not_defined())
+    }
+}
+
+fn is_numeric(type_name: &str) -> bool {
+    type_name == INT_TYPE || type_name == FLOAT_TYPE
+}
+
+impl PartialExpr<Unverified> {
+    fn check_type(self, variable_mng: &mut VariableManager) -> Result<PartialExpr<Verified>, TypeError> {
+        Ok(match self {
+            PartialExpr::Block(e) => PartialExpr::Block(e.check_type(variable_mng)?),
+            PartialExpr::If(e) => PartialExpr::If(e.check_type(variable_mng)?),
+            PartialExpr::Variable(e) => PartialExpr::Variable(e.check_type(variable_mng)?),
+            PartialExpr::FunctionCall(e) => PartialExpr::FunctionCall(e.check_type(variable_mng)?),
+            PartialExpr::Lambda(e) => PartialExpr::Lambda(e.check_type(variable_mng)?),
+            PartialExpr::Tuple(e) => PartialExpr::Tuple(e.check_type(variable_mng)?)
+        })
+    }
+}
+
 impl BlockExpr {
-    fn check_type(&self, variable_mng: &mut VariableManager) -> Result<VariableType, String> {
-        Err("Unimplemented".to_string())
+    fn check_type(self, _variable_mng: &mut VariableManager) -> Result<BlockExpr, TypeError> {
+        Err(TypeErrorKind::Unimplemented { feature: "block".to_string() }.into())
     }
 }
 
-impl IfExpr {
-    fn check_type(&self, variable_mng: &mut VariableManager) -> Result<VariableType, String> {
-        let cond_type = self.cond_expr.check_type(variable_mng)?;
-        if cond_type.info == get_condition_type() {
-            let branch_type = self.main_branch.check_type(variable_mng)?;
-            match &self.else_branch {
-                None => {
-                    if branch_type.info != TypeInfo::empty() {
-                        Err(format!("If without else, must return empty. found:{}", branch_type))
-                    } else { Ok(branch_type) }
-                }
-                Some(eb) => {
-                    let else_type = eb.check_type(variable_mng)?;
-                    if else_type.info != branch_type.info {
-                        Err(format!("Both if branches must return same type. found: main:{}, else:{}", branch_type, else_type))
-                    } else {
-                        Ok(VariableType { mutable: else_type.mutable && branch_type.mutable, info: branch_type.info })
-                    }
+impl IfExpr<Unverified> {
+    fn check_type(self, variable_mng: &mut VariableManager) -> Result<IfExpr<Verified>, TypeError> {
+        let cond_expr = self.cond_expr.check_type(variable_mng)?;
+        if variable_mng.unify(&get_condition_type(), &cond_expr.v_type.info).is_err() {
+            let found = variable_mng.resolve_variable(&cond_expr.v_type);
+            return Err(TypeError::from(TypeErrorKind::ConditionNotBool { found }).at(cond_expr.location));
+        }
+        let main_branch = self.main_branch.check_type(variable_mng)?;
+        let branch_type = main_branch.v_type.clone();
+        match self.else_branch {
+            None => {
+                if variable_mng.unify(&TypeInfo::empty(), &branch_type.info).is_err() {
+                    let found = variable_mng.resolve_variable(&branch_type);
+                    Err(TypeError::from(TypeErrorKind::IfWithoutElse { found }).at(main_branch.location))
+                } else { Ok(IfExpr { cond_expr, main_branch, else_branch: None, v_type: branch_type }) }
+            }
+            Some(eb) => {
+                let else_branch = eb.check_type(variable_mng)?;
+                let else_type = &else_branch.v_type;
+                if variable_mng.unify(&branch_type.info, &else_type.info).is_err() {
+                    let (main, other) = (variable_mng.resolve_variable(&branch_type), variable_mng.resolve_variable(else_type));
+                    Err(TypeError::from(TypeErrorKind::BranchMismatch { main, other }).at(else_branch.location))
+                } else {
+                    let v_type = VariableType { mutable: else_type.mutable && branch_type.mutable, info: branch_type.info };
+                    Ok(IfExpr { cond_expr, main_branch, else_branch: Some(else_branch), v_type })
                 }
             }
-        } else { Err(format!("Conditional expression must return {}. found:{}", get_condition_type(), cond_type)) }
+        }
     }
 }
 
-impl FunctionCallExpr {
-    fn check_type(&self, variable_mng: &mut VariableManager) -> Result<VariableType, String> {
-        match variable_mng.find_variable(&self.name) {
-            None => Err(format!("Cannot find variable: {}", &self.name)),
-            Some(VariableType { mutable: _, info: TypeInfo::Function(FuncType { params, return_type: ret }) }) => {
-                if params.len() != self.params.len() {
-                    Err(format!("Wrong number of params. expected:{}, found:{}", params.len(), self.params.len()))
-                } else {
-                    for (vt, e) in params.iter().zip(&self.params) {
-                        e.check_type(variable_mng)?.check_expected(vt)?;
-                    }
-                    Ok(*ret)
-                }
+impl FunctionCallExpr<Unverified> {
+    fn check_type(self, variable_mng: &mut VariableManager) -> Result<FunctionCallExpr<Verified>, TypeError> {
+        let location = self.location;
+        self.check_call(variable_mng).map_err(|e| e.at(location))
+    }
+
+    fn check_call(self, variable_mng: &mut VariableManager) -> Result<FunctionCallExpr<Verified>, TypeError> {
+        let (callee, binding) = variable_mng.find_variable(&self.name, self.location).ok_or(TypeErrorKind::UnknownVariable { name: self.name.to_string() })?;
+        let FuncType { params, return_type: ret } = match variable_mng.resolve(&callee.info) {
+            TypeInfo::Function(func) => func,
+            TypeInfo::Var(_) => {
+                let func = variable_mng.func_type(self.params.len());
+                variable_mng.unify(&TypeInfo::Function(func.clone()), &callee.info)?;
+                func
+            }
+            found => return Err(TypeErrorKind::NotFunction { name: self.name.to_string(), found }.into())
+        };
+        if params.len() != self.params.len() {
+            Err(TypeErrorKind::WrongArgumentCount { expected: params.len(), found: self.params.len() }.into())
+        } else {
+            let mut checked = Vec::with_capacity(params.len());
+            for (vt, e) in params.iter().zip(self.params) {
+                let e = e.check_type(variable_mng)?;
+                variable_mng.expect(e.v_type.clone(), vt).map_err(|err| err.at(e.location))?;
+                checked.push(e);
             }
-            Some(t) => Err(format!("Variable {} is not function: found type:{}", &self.name, t)),
+            Ok(FunctionCallExpr { name: self.name, params: checked, location: self.location, binding, v_type: *ret, tail: false })
         }
     }
 }
 
-impl VariableExpr {
-    fn check_type(&self, variable_mng: &mut VariableManager) -> Result<VariableType, String> {
+impl VariableExpr<Unverified> {
+    fn check_type(self, variable_mng: &mut VariableManager) -> Result<VariableExpr<Verified>, TypeError> {
         match self {
-            VariableExpr::Variable(name) => variable_mng.find_variable(name).ok_or(
-                format!("Cannot find variable: {}", name)
-            ),
-            VariableExpr::Constant(cv) => Ok(VariableType { mutable: true, info: get_type(cv) })
+            VariableExpr::Variable { name, location, .. } => match variable_mng.find_variable(&name, location) {
+                Some((_, Binding::Builtin(_))) => Err(TypeError::from(builtin_as_value(name)).at(location)),
+                Some((v_type, binding)) => Ok(VariableExpr::Variable { name, location, binding, v_type }),
+                None => Err(TypeError::from(TypeErrorKind::UnknownVariable { name: name.to_string() }).at(location))
+            },
+            VariableExpr::Constant(cv) => Ok(VariableExpr::Constant(cv))
         }
     }
 }
 
-impl FunctionDef {
-    fn check_type(&self, variable_mng: &mut VariableManager) -> Result<VariableType, String> {
-        let mut local_variables = VariableManager::new();
-        for (name, mutable) in &self.closure {
-            match variable_mng.find_variable(name) {
-                Some(vt) => {
-                    if vt.check_mutability(*mutable) {
-                        local_variables.add_variable(name.to_owned(), VariableType { mutable: *mutable, info: vt.info })
-                    }
+impl FunctionDef<Unverified> {
+    /// Replaces the missing parameter and return types with type variables.
+    fn fill_unknown_types(&mut self, variable_mng: &mut VariableManager) {
+        for (_, param_type) in &mut self.parameters {
+            param_type.info = variable_mng.known_or_fresh(&param_type.info);
+        }
+        self.return_type.info = variable_mng.known_or_fresh(&self.return_type.info);
+    }
+
+    fn check_type(self, variable_mng: &mut VariableManager) -> Result<FunctionDef<Verified>, TypeError> {
+        let location = self.location;
+        self.check_lambda(variable_mng).map_err(|e| e.at(location))
+    }
+
+    fn check_lambda(mut self, variable_mng: &mut VariableManager) -> Result<FunctionDef<Verified>, TypeError> {
+        if self.memo {
+            // Cached results would hide the changes of mutable variables.
+            let mutable = self.parameters.iter().filter(|(_, vt)| vt.mutable).map(|(name, _)| name.clone())
+                .chain(self.closure.iter().filter(|c| c.mutable).map(|c| c.name.clone()))
+                .next();
+            if let Some(name) = mutable {
+                return Err(TypeErrorKind::MutableMemo { name: name.to_string() }.into());
+            }
+        }
+        self.fill_unknown_types(variable_mng);
+        let self_type = VariableType { mutable: false, info: TypeInfo::Function(FuncType::from(&self)) };
+        let mut closure = Vec::new();
+        let mut closure_types = Vec::new();
+        for Capture { name, mutable, .. } in self.closure {
+            match variable_mng.find_variable(&name, self.location) {
+                Some((_, Binding::Builtin(_))) => return Err(builtin_as_value(name).into()),
+                Some((vt, binding)) if vt.check_mutability(mutable) => {
+                    closure_types.push(VariableType { mutable, info: vt.info });
+                    closure.push(Capture { name, mutable, binding });
                 }
-                None => return Err(format!("Cannot find variable: {}", name))
+                Some(_) => return Err(TypeErrorKind::NotMutable { name: name.to_string() }.into()),
+                None => return Err(TypeErrorKind::UnknownVariable { name: name.to_string() }.into())
             }
         };
+        variable_mng.enter_function();
         for (name, param_type) in &self.parameters {
-            local_variables.add_variable(name.to_owned(), (*param_type).clone())
+            variable_mng.add_variable(name.clone(), param_type.clone())
         }
-        local_variables.add_variable("self_fn".to_string(), VariableType { mutable: false, info: TypeInfo::Function(self.into()) });
-        //println!("{:?}",local_variables);
-        let ret_type = self.expr.check_type(&mut local_variables)?;
-        ret_type.check_expected(&self.return_type)?;
-        Ok(VariableType { mutable: true, info: TypeInfo::Function(self.into()) })
+        for (c, vt) in closure.iter().zip(closure_types) {
+            variable_mng.add_variable(c.name.clone(), vt)
+        }
+        variable_mng.add_self_fn(self_type);
+        let expr = Rc::unwrap_or_clone(self.expr).check_type(variable_mng);
+        let uses_self = variable_mng.exit_function();
+        let mut expr = expr?;
+        expr.mark_tail_calls();
+        variable_mng.expect(expr.v_type.clone(), &self.return_type).map_err(|e| e.at(expr.location))?;
+        // The effects depend on the functions called by name, so they are inferred when their effects are known.
+        Ok(FunctionDef { parameters: self.parameters, closure, return_type: self.return_type, expr: Rc::new(expr), location: self.location, memo: self.memo, pure: self.pure, effects: Effects::PURE, uses_self })
     }
 }
 
-impl TupleDef {
-    fn check_type(&self, variable_mng: &mut VariableManager) -> Result<VariableType, String> {
+impl TupleDef<Unverified> {
+    fn check_type(self, variable_mng: &mut VariableManager) -> Result<TupleDef<Verified>, TypeError> {
+        let mut items = Vec::new();
         let mut types = Vec::new();
-        for e in &self.items {
-            let t = e.check_type(variable_mng)?;
-            types.push(t.info);
+        for e in self.items {
+            let e = e.check_type(variable_mng)?;
+            types.push(e.v_type.info.clone());
+            items.push(e);
         }
-        Ok(VariableType { mutable: false, info: TypeInfo::Tuple(types) })
+        Ok(TupleDef { items, v_type: VariableType { mutable: false, info: TypeInfo::Tuple(types) } })
     }
 }
 
+/// Builtin functions can only be called, they are not values.
+fn builtin_as_value(name: Symbol) -> TypeErrorKind {
+    TypeErrorKind::Unimplemented { feature: format!("builtin function {} as a value", name) }
+}
+
 fn get_type(c: &ConstantValue) -> TypeInfo {
     TypeInfo::Struct(match c {
         ConstantValue::Integer(_) => INT_TYPE,
@@ -171,4 +419,3 @@ fn get_type(c: &ConstantValue) -> TypeInfo {
 fn get_condition_type() -> TypeInfo {
     TypeInfo::Struct(BOOL_TYPE.to_string())
 }
-
@@ -1,61 +1,370 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::fmt::{Debug, Formatter};
 
+use crate::ast::effects::Effects;
+use crate::ast::expr::Operand;
+use crate::ast::location::Location;
 use crate::ast::Module;
 use crate::ast::states::Verified;
-use crate::ast::types::VariableType;
+use crate::ast::types::{FuncType, TypeInfo, VariableType};
+use crate::ast::symbol::{Symbol, SymbolTable};
+use crate::ast::variable::{qualified_name, Binding, Link};
+use crate::std_library::builtins::Builtin;
+use crate::verify::{check_defined, INT_TYPE, SELF_FN};
+use crate::verify::error::{TypeError, TypeErrorKind};
 
+/// Type of a variable. The `generic` type variables are replaced with fresh ones every time the variable is used.
+#[derive(Clone)]
+struct Scheme {
+    generic: Vec<usize>,
+    /// Generic type variables, which must not be functions, because they are compared for equality.
+    comparable: Vec<usize>,
+    v_type: VariableType,
+    source: Source,
+}
+
+/// Where the variable of a scheme is defined.
+#[derive(Clone)]
+enum Source {
+    Bound(Binding),
+    /// Defined outside of the module. It gets a slot in the globals of the module when it is first used.
+    Linked(Link),
+}
+
+/// Function being checked.
+struct Scope {
+    /// Index of the first layer of the function. Layers below it, except the global one, are not visible.
+    first_layer: usize,
+    /// Number of local variables, which is the slot of the next one.
+    locals: usize,
+    /// Slot of `self_fn`, and if the body used it.
+    self_slot: Option<usize>,
+    uses_self: bool,
+}
+
+/// Keeps track of the visible variables and of the type variables during the type inference.
 pub(crate) struct VariableManager {
-    variables: Vec<HashMap<String, VariableType>>,
+    variables: Vec<HashMap<Symbol, Scheme>>,
+    scopes: Vec<Scope>,
+    substitution: Vec<Option<TypeInfo>>,
+    /// Types of which the operands must be defined. Checked when the types are generalized.
+    operands: Vec<(TypeInfo, Operand, Location)>,
+    /// Type variables compared for equality. They stay generic, but can not be functions.
+    comparable: HashSet<usize>,
+    /// Effects of the global and module variables defined by lambdas, by the name they are called with.
+    effects: HashMap<Symbol, Effects>,
+    /// Slots of the variables of the module in its globals.
+    globals: HashMap<Symbol, usize>,
+    /// Variables used by the module, which are defined outside of it. Their slots follow the variables of the module.
+    links: Vec<Link>,
+    /// Table of the symbols of the checked module.
+    symbols: SymbolTable,
 }
 
 impl VariableManager {
-    pub fn new() -> Self {
+    /// Creates a manager, where only the builtin functions are visible. `symbols` is the table of the checked module.
+    pub fn new(symbols: SymbolTable) -> Self {
+        let builtins = Builtin::ALL.iter()
+            .map(|b| (symbols.intern(b.name()), Scheme {
+                generic: Vec::new(),
+                comparable: Vec::new(),
+                v_type: VariableType { mutable: false, info: TypeInfo::Function(b.func_type()) },
+                source: Source::Bound(Binding::Builtin(*b)),
+            }))
+            .collect();
         VariableManager {
-            variables: vec![HashMap::new()]
+            variables: vec![builtins],
+            scopes: Vec::new(),
+            substitution: Vec::new(),
+            operands: Vec::new(),
+            comparable: HashSet::new(),
+            effects: HashMap::new(),
+            globals: HashMap::new(),
+            links: Vec::new(),
+            symbols,
         }
     }
 
-    pub fn add_variable(&mut self, name: String, variable_type: VariableType) {
-        self.variables.last_mut().unwrap().insert(name, variable_type);
+    /// Adds a variable of the current scope: a global one at the top level, otherwise a local one in the next slot.
+    pub fn add_variable(&mut self, name: Symbol, variable_type: VariableType) {
+        let binding = match self.scopes.last_mut() {
+            Some(scope) => {
+                scope.locals += 1;
+                Binding::Local { slot: scope.locals - 1 }
+            }
+            None => Binding::Global { index: self.globals[&name] }
+        };
+        let source = Source::Bound(binding);
+        self.variables.last_mut().unwrap().insert(name, Scheme { generic: Vec::new(), comparable: Vec::new(), v_type: variable_type, source });
+    }
+
+    /// Gives the variables of the module their slots in its globals, in the order they are stored and run.
+    pub fn declare_globals(&mut self, names: impl Iterator<Item=Symbol>) {
+        self.globals = names.enumerate().map(|(index, name)| (name, index)).collect();
     }
 
-    pub fn add_module(&mut self, module: &Module<Verified>) {
-        for v in &module.variables {
-            self.add_variable(v.name.clone(), v.variable.v_type.clone())
+    /// Adds `self_fn` of the function being checked. `exit_function` tells if the body used it.
+    pub fn add_self_fn(&mut self, variable_type: VariableType) {
+        self.add_variable(self.symbols.intern(SELF_FN), variable_type);
+        let scope = self.scopes.last_mut().expect("not in function");
+        scope.self_slot = Some(scope.locals - 1);
+    }
+
+    /// Adds a linked variable whose type variables are all generic. The `comparable` ones can not be functions.
+    pub fn add_generic(&mut self, name: Symbol, variable_type: VariableType, comparable: Vec<usize>, link: Link) {
+        let mut generic = Vec::new();
+        variable_type.info.collect_vars(&mut generic);
+        self.variables.last_mut().unwrap().insert(name, Scheme { generic, comparable, v_type: variable_type, source: Source::Linked(link) });
+    }
+
+    /// Adds a function registered by the host.
+    pub fn add_host(&mut self, name: Symbol, func_type: FuncType) {
+        let link = Link::Host(name.clone());
+        self.add_generic(name, VariableType { mutable: false, info: TypeInfo::Function(func_type) }, Vec::new(), link)
+    }
+
+    /// Variables used by the module, which are defined outside of it, in the order of their slots.
+    pub fn links(&self) -> Vec<Link> {
+        self.links.clone()
+    }
+
+    /// Adds the public variables of the module with qualified names: `module_name::variable`.
+    pub fn add_module(&mut self, module_name: &str, module: &Module<Verified>) {
+        for v in module.variables.iter().filter(|v| v.public) {
+            let link = Link::Module { module: self.symbols.intern(module_name), name: v.name.clone() };
+            let name = self.symbols.intern(&qualified_name(module_name, &v.name.name()));
+            self.add_generic(name.clone(), v.variable.v_type.clone(), v.variable.comparable.clone(), link);
+            if let Some(fd) = v.variable.value.as_lambda() {
+                self.add_effects(name, fd.effects)
+            }
         }
     }
 
-    pub fn find_variable(&self, name: &str) -> Option<VariableType> {
-        for layer in self.variables.iter().rev() {
-            match layer.get(name) {
-                Some(vt) => return Some((*vt).clone()),
-                _ => ()
-            };
+    pub fn add_effects(&mut self, name: Symbol, effects: Effects) {
+        self.effects.insert(name, effects);
+    }
+
+    /// Effects of calling a global or module variable. `None` if the variable is not defined by a lambda.
+    pub fn effects(&self, name: &Symbol) -> Option<Effects> {
+        self.effects.get(name).copied()
+    }
+
+    /// `location` is the use of the variable, where the requirements of its generic type are checked.
+    pub fn find_variable(&mut self, name: &Symbol, location: Location) -> Option<(VariableType, Binding)> {
+        let first_visible = self.scopes.last().map(|s| s.first_layer).unwrap_or(0);
+        let scheme = self.variables[first_visible..].iter().rev()
+            .chain(self.variables.first())
+            .find_map(|layer| layer.get(name))?
+            .clone();
+        let binding = match scheme.source.clone() {
+            Source::Bound(binding) => binding,
+            Source::Linked(link) => self.link(link)
         };
-        None
+        if let (Binding::Local { slot }, Some(scope)) = (&binding, self.scopes.last_mut()) {
+            scope.uses_self |= scope.self_slot == Some(*slot);
+        }
+        Some((self.instantiate(&scheme, location), binding))
     }
 
-    pub fn add_layer(&mut self) {
-        self.variables.push(HashMap::new())
+    /// Binding of a linked variable: its slot after the variables of the module.
+    fn link(&mut self, link: Link) -> Binding {
+        let index = match self.links.iter().position(|l| *l == link) {
+            Some(index) => index,
+            None => {
+                self.links.push(link.clone());
+                self.links.len() - 1
+            }
+        } + self.globals.len();
+        match link {
+            Link::Module { .. } => Binding::Global { index },
+            Link::Host(_) => Binding::Host { index }
+        }
     }
 
-    pub fn pop_layer(&mut self) {
-        self.variables.pop();
-        if self.variables.is_empty() {
-            self.add_layer()
+    /// Starts checking a function body. Only the global variables are visible,
+    /// the local ones must be captured in the closure.
+    pub fn enter_function(&mut self) {
+        self.scopes.push(Scope { first_layer: self.variables.len(), locals: 0, self_slot: None, uses_self: false });
+        self.variables.push(HashMap::new());
+    }
+
+    /// Returns if the body used `self_fn`.
+    pub fn exit_function(&mut self) -> bool {
+        let scope = self.scopes.pop().expect("not in function");
+        self.variables.truncate(scope.first_layer);
+        scope.uses_self
+    }
+
+    pub fn fresh_type(&mut self) -> TypeInfo {
+        self.substitution.push(None);
+        TypeInfo::Var(self.substitution.len() - 1)
+    }
+
+    /// Replaces a missing type with a fresh type variable.
+    pub fn known_or_fresh(&mut self, info: &TypeInfo) -> TypeInfo {
+        match info {
+            TypeInfo::Unknown => self.fresh_type(),
+            _ => info.clone()
         }
     }
+
+    /// Replaces the generic type variables with fresh ones. The fresh comparable ones must not become functions.
+    fn instantiate(&mut self, scheme: &Scheme, location: Location) -> VariableType {
+        if scheme.generic.is_empty() {
+            return scheme.v_type.clone();
+        }
+        let fresh: HashMap<usize, TypeInfo> = scheme.generic.iter().map(|id| (*id, self.fresh_type())).collect();
+        for id in &scheme.comparable {
+            self.operands.push((fresh[id].clone(), Operand::Eq, location))
+        }
+        scheme.v_type.substitute(&mut |id| fresh.get(&id).cloned().unwrap_or(TypeInfo::Var(id)))
+    }
+
+    /// Applies the current substitution to the type.
+    pub fn resolve(&self, info: &TypeInfo) -> TypeInfo {
+        resolve(&self.substitution, info)
+    }
+
+    pub fn resolve_variable(&self, vt: &VariableType) -> VariableType {
+        VariableType { mutable: vt.mutable, info: self.resolve(&vt.info) }
+    }
+
+    fn shallow_resolve(&self, info: &TypeInfo) -> TypeInfo {
+        match info {
+            TypeInfo::Var(id) => match &self.substitution[*id] {
+                Some(t) => self.shallow_resolve(t),
+                None => info.clone()
+            },
+            _ => info.clone()
+        }
+    }
+
+    /// Makes the types equal. The error contains both of the complete types.
+    pub fn unify(&mut self, expected: &TypeInfo, found: &TypeInfo) -> Result<(), TypeError> {
+        self.unify_parts(expected, found).map_err(|infinite| {
+            let (expected, found) = (self.resolve(expected), self.resolve(found));
+            if infinite {
+                TypeErrorKind::InfiniteType { expected, found }.into()
+            } else {
+                TypeErrorKind::Mismatch { expected, found }.into()
+            }
+        })
+    }
+
+    /// The error tells if the types could be unified only with an infinite type.
+    fn unify_parts(&mut self, a: &TypeInfo, b: &TypeInfo) -> Result<(), bool> {
+        match (self.shallow_resolve(a), self.shallow_resolve(b)) {
+            (TypeInfo::Var(x), TypeInfo::Var(y)) if x == y => Ok(()),
+            (TypeInfo::Var(x), t) | (t, TypeInfo::Var(x)) => {
+                if self.occurs(x, &t) {
+                    Err(true)
+                } else {
+                    self.substitution[x] = Some(t);
+                    Ok(())
+                }
+            }
+            (TypeInfo::Struct(x), TypeInfo::Struct(y)) if x == y => Ok(()),
+            (TypeInfo::Tuple(x), TypeInfo::Tuple(y)) if x.len() == y.len() => {
+                x.iter().zip(&y).try_for_each(|(a, b)| self.unify_parts(a, b))
+            }
+            (TypeInfo::Function(x), TypeInfo::Function(y)) if x.params.len() == y.params.len() => {
+                if x.params.iter().zip(&y.params).any(|(a, b)| a.mutable != b.mutable) || x.return_type.mutable != y.return_type.mutable {
+                    return Err(false);
+                }
+                x.params.iter().zip(&y.params).try_for_each(|(a, b)| self.unify_parts(&a.info, &b.info))?;
+                self.unify_parts(&x.return_type.info, &y.return_type.info)
+            }
+            _ => Err(false)
+        }
+    }
+
+    fn occurs(&self, id: usize, info: &TypeInfo) -> bool {
+        let mut vars = Vec::new();
+        self.resolve(info).collect_vars(&mut vars);
+        vars.contains(&id)
+    }
+
+    /// Checks that the found type can be stored to a place of the expected type.
+    /// A missing expected type is taken from the found type.
+    pub fn expect(&mut self, found: VariableType, expected: &VariableType) -> Result<VariableType, TypeError> {
+        if expected.info != TypeInfo::Unknown {
+            self.unify(&expected.info, &found.info)?;
+        }
+        if found.check_mutability(expected.mutable) {
+            Ok(VariableType { mutable: expected.mutable, info: found.info })
+        } else {
+            Err(TypeErrorKind::IncorrectMutability { expected: self.resolve_variable(expected), found: self.resolve_variable(&found) }.into())
+        }
+    }
+
+    /// Requires `operand` to be defined for the operands of the type.
+    /// The known part of the type is checked now, the type variables in it when the types are generalized.
+    pub fn require_operand(&mut self, info: TypeInfo, operand: Operand, location: Location) -> Result<(), TypeError> {
+        let resolved = self.resolve(&info);
+        check_defined(operand, &resolved).map_err(|e| TypeError::from(e).at(location))?;
+        let mut vars = Vec::new();
+        resolved.collect_vars(&mut vars);
+        if !vars.is_empty() {
+            self.operands.push((info, operand, location))
+        }
+        Ok(())
+    }
+
+    /// Checks the pending operand requirements. Type variables which are still unknown default to `Int`,
+    /// except the ones only compared for equality, which stay generic.
+    pub fn check_operands(&mut self) -> Result<(), TypeError> {
+        let (equality, others): (Vec<_>, Vec<_>) = std::mem::take(&mut self.operands).into_iter()
+            .partition(|(_, operand, _)| matches!(operand, Operand::Eq | Operand::Ne));
+        // The defaults of the other operands are chosen first, because they may make the compared types known.
+        for (info, operand, location) in others.into_iter().chain(equality) {
+            let mut vars = Vec::new();
+            self.resolve(&info).collect_vars(&mut vars);
+            match operand {
+                Operand::Eq | Operand::Ne => self.comparable.extend(vars),
+                _ => for id in vars {
+                    self.unify(&TypeInfo::Struct(INT_TYPE.to_string()), &TypeInfo::Var(id)).map_err(|e| e.at(location))?
+                }
+            }
+            check_defined(operand, &self.resolve(&info)).map_err(|e| TypeError::from(e).at(location))?
+        }
+        Ok(())
+    }
+
+    /// Resolves the types of a finished global variable and makes its remaining type variables generic.
+    /// Returns the type and its comparable type variables.
+    pub fn generalize(&mut self, name: &Symbol) -> (VariableType, Vec<usize>) {
+        let scheme = self.variables[0].get_mut(name).unwrap();
+        let v_type = VariableType { mutable: scheme.v_type.mutable, info: resolve(&self.substitution, &scheme.v_type.info) };
+        scheme.generic.clear();
+        v_type.info.collect_vars(&mut scheme.generic);
+        scheme.comparable = scheme.generic.iter().copied().filter(|id| self.comparable.contains(id)).collect();
+        scheme.v_type = v_type.clone();
+        (v_type, scheme.comparable.clone())
+    }
+
+    pub fn func_type(&mut self, params: usize) -> FuncType {
+        FuncType {
+            params: (0..params).map(|_| VariableType { mutable: false, info: self.fresh_type() }).collect(),
+            return_type: Box::new(VariableType { mutable: false, info: self.fresh_type() }),
+        }
+    }
+}
+
+fn resolve(substitution: &[Option<TypeInfo>], info: &TypeInfo) -> TypeInfo {
+    info.substitute(&mut |id| match &substitution[id] {
+        Some(t) => resolve(substitution, t),
+        None => TypeInfo::Var(id)
+    })
 }
 
 impl Debug for VariableManager {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         for l in &self.variables {
-            for (name, vt) in l {
-                write!(f, "{}:{} ", name, vt)?
+            for (name, scheme) in l {
+                write!(f, "{}:{} ", name, scheme.v_type)?
             }
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,144 @@
+use std::rc::Rc;
+
+use crate::ast::expr::{Expr, FunctionCallExpr, FunctionDef, IfExpr, PartialExpr, VariableExpr};
+use crate::ast::location::Location;
+use crate::ast::states::Verified;
+use crate::ast::symbol::Symbol;
+use crate::ast::variable::Binding;
+use crate::vm::function::{CallSite, Function, Instruction};
+
+/// Compiles a top-level expression: the value of a module variable or an expression run by the host.
+pub fn compile(expr: &Expr<Verified>) -> Rc<Function> {
+    let mut compiler = Compiler { function: Function::default() };
+    compiler.expr(expr);
+    compiler.emit(Instruction::Return, expr.location);
+    Rc::new(compiler.function)
+}
+
+struct Compiler {
+    function: Function,
+}
+
+impl Compiler {
+    fn lambda(def: &FunctionDef<Verified>) -> Function {
+        let params = def.parameters.len();
+        let captures = def.closure.len();
+        let function = Function { params, captures, uses_self: def.uses_self, memo: def.memo, ..Function::default() };
+        let mut compiler = Compiler { function };
+        compiler.expr(&def.expr);
+        compiler.emit(Instruction::Return, def.expr.location);
+        compiler.function
+    }
+
+    fn emit(&mut self, instruction: Instruction, location: Location) -> usize {
+        self.function.code.push(instruction);
+        self.function.locations.push(location);
+        self.function.code.len() - 1
+    }
+
+    /// Makes the jump at `index` jump to the next instruction.
+    fn patch(&mut self, index: usize) {
+        let next = self.function.code.len();
+        match &mut self.function.code[index] {
+            Instruction::Jump(target) | Instruction::JumpIfFalse(target) => *target = next,
+            _ => unreachable!("only jumps are patched")
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr<Verified>) {
+        self.emit(Instruction::Step, expr.location);
+        let mut exprs = expr.exprs.iter();
+        if let Some(first) = exprs.next() {
+            self.partial(first, expr.location);
+        }
+        for (e, o) in exprs.zip(&expr.operands) {
+            self.partial(e, expr.location);
+            self.emit(Instruction::Operation(*o), expr.location);
+        }
+    }
+
+    /// `location` is the location of the expression containing `expr`.
+    fn partial(&mut self, expr: &PartialExpr<Verified>, location: Location) {
+        match expr {
+            PartialExpr::Block(_) => {
+                self.emit(Instruction::Unimplemented("block"), location);
+            }
+            PartialExpr::If(e) => self.if_expr(e, location),
+            PartialExpr::FunctionCall(e) => self.call(e, location),
+            PartialExpr::Variable(VariableExpr::Variable { name, location, binding, .. }) => self.load(name, binding, *location),
+            PartialExpr::Variable(VariableExpr::Constant(v)) => {
+                self.function.constants.push(v.clone());
+                self.emit(Instruction::Constant(self.function.constants.len() - 1), location);
+            }
+            PartialExpr::Lambda(def) => {
+                for c in &def.closure {
+                    self.load(&c.name, &c.binding, def.location)
+                }
+                self.function.functions.push(Rc::new(Compiler::lambda(def)));
+                let function = self.function.functions.len() - 1;
+                self.emit(Instruction::Closure { function, captures: def.closure.len() }, location);
+            }
+            PartialExpr::Tuple(t) if t.items.is_empty() => {
+                self.emit(Instruction::Empty, location);
+            }
+            PartialExpr::Tuple(t) => {
+                for item in &t.items {
+                    self.expr(item)
+                }
+                self.emit(Instruction::Tuple(t.items.len()), location);
+            }
+        }
+    }
+
+    fn load(&mut self, name: &Symbol, binding: &Binding, location: Location) {
+        let index = match *binding {
+            Binding::Local { slot } => {
+                self.emit(Instruction::Local(slot), location);
+                return;
+            }
+            Binding::Global { index } | Binding::Host { index } => index,
+            Binding::Builtin(_) => unreachable!("builtins are not values")
+        };
+        let name = match self.function.names.iter().position(|n| n == name) {
+            Some(name) => name,
+            None => {
+                self.function.names.push(name.clone());
+                self.function.names.len() - 1
+            }
+        };
+        self.emit(Instruction::Global { index, name }, location);
+    }
+
+    fn if_expr(&mut self, e: &IfExpr<Verified>, location: Location) {
+        self.expr(&e.cond_expr);
+        let jump_if_false = self.emit(Instruction::JumpIfFalse(0), e.cond_expr.location);
+        self.expr(&e.main_branch);
+        let jump = self.emit(Instruction::Jump(0), location);
+        self.patch(jump_if_false);
+        match &e.else_branch {
+            Some(else_branch) => self.expr(else_branch),
+            None => {
+                self.emit(Instruction::Empty, location);
+            }
+        }
+        self.patch(jump);
+    }
+
+    fn call(&mut self, e: &FunctionCallExpr<Verified>, location: Location) {
+        let params = e.params.len();
+        if let Binding::Builtin(builtin) = e.binding {
+            for p in &e.params {
+                self.expr(p)
+            }
+            self.emit(Instruction::Builtin { builtin, params }, e.location);
+            return;
+        }
+        self.load(&e.name, &e.binding, e.location);
+        for p in &e.params {
+            self.expr(p)
+        }
+        self.function.calls.push(CallSite { name: e.name.clone(), location: e.location });
+        let call = self.function.calls.len() - 1;
+        self.emit(if e.tail { Instruction::TailCall { call, params } } else { Instruction::Call { call, params } }, location);
+    }
+}
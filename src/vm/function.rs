@@ -0,0 +1,109 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+use crate::ast::expr::Operand;
+use crate::ast::location::Location;
+use crate::ast::symbol::Symbol;
+use crate::ast::variable::ConstantValue;
+use crate::run_env::RefDataObj;
+use crate::run_env::memo::Memo;
+use crate::run_env::variable_stack::Globals;
+use crate::std_library::builtins::Builtin;
+use crate::std_library::StdMod;
+
+/// Instruction of the stack machine. Operands are popped from the value stack, and the result is pushed to it.
+#[derive(Debug, Clone, Copy)]
+pub enum Instruction {
+    /// Starts an evaluation step, which consumes fuel.
+    Step,
+    /// Pushes the constant with the index.
+    Constant(usize),
+    /// Pushes the local variable in the slot of the frame.
+    Local(usize),
+    /// Pushes the global variable in the slot `index`. `name` is the index of its name.
+    Global { index: usize, name: usize },
+    /// Pops the right and the left operand.
+    Operation(Operand),
+    /// Pops the items of a tuple.
+    Tuple(usize),
+    Empty,
+    /// Pops the captured variables of the nested function with the index and pushes a function value of it.
+    Closure { function: usize, captures: usize },
+    /// Pops the condition and jumps to the instruction if it is false.
+    JumpIfFalse(usize),
+    Jump(usize),
+    /// Pops the parameters and the function. `call` is the index of the call site.
+    Call { call: usize, params: usize },
+    /// Like `Call`, but the called function replaces the running one in its frame.
+    TailCall { call: usize, params: usize },
+    /// Pops the parameters of the builtin.
+    Builtin { builtin: Builtin, params: usize },
+    /// Pops the result of the running function.
+    Return,
+    Unimplemented(&'static str),
+}
+
+/// Called function and the location of the call, reported in the stack of runtime errors.
+#[derive(Debug, Clone)]
+pub struct CallSite {
+    pub name: Symbol,
+    pub location: Location,
+}
+
+/// Bytecode of a function or of a top-level expression.
+/// The local variables of a function are the parameters, the captured variables and `self_fn`, in that order.
+#[derive(Debug, Default)]
+pub struct Function {
+    pub(crate) params: usize,
+    pub(crate) captures: usize,
+    /// `self_fn` is created for a call only if the function uses it.
+    pub(crate) uses_self: bool,
+    /// The function values of it cache their results.
+    pub(crate) memo: bool,
+    pub(crate) code: Vec<Instruction>,
+    /// Location of each instruction, attached to the errors it causes.
+    pub(crate) locations: Vec<Location>,
+    pub(crate) constants: Vec<ConstantValue>,
+    pub(crate) names: Vec<Symbol>,
+    pub(crate) calls: Vec<CallSite>,
+    pub(crate) functions: Vec<Rc<Function>>,
+}
+
+/// Function value of the bytecode backend.
+pub struct Closure<S: StdMod> {
+    pub(crate) function: Rc<Function>,
+    pub(crate) captures: Vec<RefDataObj<S>>,
+    pub(crate) globals: Globals<S>,
+    pub(crate) memo: Option<Rc<Memo<S>>>,
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, instruction) in self.code.iter().enumerate() {
+            write!(f, "{:>4} ", i)?;
+            match instruction {
+                Instruction::Step => writeln!(f, "STEP"),
+                Instruction::Constant(c) => writeln!(f, "CONST {:?}", self.constants[*c]),
+                Instruction::Local(slot) => writeln!(f, "LOCAL {}", slot),
+                Instruction::Global { name, .. } => writeln!(f, "GLOBAL {}", self.names[*name]),
+                Instruction::Operation(o) => writeln!(f, "{:?}", o),
+                Instruction::Tuple(n) => writeln!(f, "TUPLE {}", n),
+                Instruction::Empty => writeln!(f, "EMPTY"),
+                Instruction::Closure { function, captures } => writeln!(f, "CLOSURE fn{} {}", function, captures),
+                Instruction::JumpIfFalse(target) => writeln!(f, "JUMPIFFALSE {}", target),
+                Instruction::Jump(target) => writeln!(f, "JUMP {}", target),
+                Instruction::Call { call, params } => writeln!(f, "CALL {} {}", self.calls[*call].name, params),
+                Instruction::TailCall { call, params } => writeln!(f, "TAILCALL {} {}", self.calls[*call].name, params),
+                Instruction::Builtin { builtin, params } => writeln!(f, "BUILTIN {} {}", builtin.name(), params),
+                Instruction::Return => writeln!(f, "RETURN"),
+                Instruction::Unimplemented(feature) => writeln!(f, "UNIMPLEMENTED {}", feature),
+            }?
+        }
+        for (i, function) in self.functions.iter().enumerate() {
+            writeln!(f, "fn{} {}<{}>[{}]:", i, if function.memo { "memo " } else { "" }, function.params, function.captures)?;
+            write!(f, "{}", function)?
+        }
+        Ok(())
+    }
+}
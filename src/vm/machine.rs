@@ -0,0 +1,305 @@
+use std::ops::Deref;
+use std::rc::Rc;
+
+use crate::ast::variable::ConstantValue;
+use crate::run_env::{calculate, DataObj, Flow, RefDataObj};
+use crate::run_env::context::{DepthGuard, RunContext};
+use crate::run_env::error::{RuntimeError, RuntimeErrorKind};
+use crate::run_env::memo::MemoCall;
+use crate::run_env::variable_stack::{Globals, VariableStack};
+use crate::std_library::{BoolType, FuncType, StdMod};
+use crate::vm::function::{CallSite, Closure, Function, Instruction};
+
+/// Runs a top-level expression compiled with `compile`.
+pub(crate) fn run<S: StdMod>(function: Rc<Function>, known_variables: &VariableStack<S>) -> Result<RefDataObj<S>, RuntimeError> {
+    let mut machine = Machine { context: known_variables.context(), stack: Vec::new(), frames: Vec::new(), memos: Vec::new() };
+    machine.frames.push(Frame { function, globals: known_variables.globals(), start: 0, base: 0, ip: 0, call: None, tail_call: None, memos: Vec::new(), _call: None });
+    match machine.execute()? {
+        Flow::Value(value) => Ok(value),
+        Flow::TailCall { .. } => unreachable!("top-level code has no tail calls")
+    }
+}
+
+/// Calls a function of the bytecode backend. Like the tree-walker, the call is an evaluation step.
+/// The call continues a chain of tail calls made by the caller in a loop: `memos` are the calls of memoized functions
+/// waiting for the result of the chain, and `tail_call` is the last tail call, reported in the stack of errors.
+/// A tail call of the function to a function of the tree-walker is returned to the caller with the calls in `memos`,
+/// so the tail calls between the backends do not nest.
+pub(crate) fn call<S: StdMod>(f: &S::FUNC, params: Vec<RefDataObj<S>>, context: &Rc<RunContext>, memos: &mut Vec<MemoCall<S>>, tail_call: Option<CallSite>) -> Result<Flow<S>, RuntimeError> {
+    let closure = f.as_compiled().expect("function of the bytecode backend");
+    let mut machine = Machine { context, stack: params, frames: Vec::new(), memos: Vec::new() };
+    let memos_of_chain = std::mem::take(memos);
+    let frame = Frame { function: closure.function.clone(), globals: closure.globals.clone(), start: 0, base: 0, ip: 0, call: None, tail_call, memos: memos_of_chain, _call: None };
+    machine.frames.push(frame);
+    if let Some(value) = machine.cached(closure, 0) {
+        machine.ret(value.clone());
+        return Ok(Flow::Value(value));
+    }
+    context.step().and_then(|_| machine.enter(f, closure)).map_err(|e| machine.unwind(e))?;
+    let flow = machine.execute()?;
+    *memos = std::mem::take(&mut machine.memos);
+    Ok(flow)
+}
+
+/// Call of a script function in progress.
+struct Frame<'c, S: StdMod> {
+    function: Rc<Function>,
+    globals: Globals<S>,
+    /// Start of the values of the frame in the value stack: the called function, if the machine called it.
+    start: usize,
+    /// Index of the first local variable in the value stack.
+    base: usize,
+    /// Index of the next instruction. Zero before the first instruction, so errors have no location of the frame.
+    ip: usize,
+    /// Call site, which created the frame. `None` for the first frame of the machine.
+    call: Option<CallSite>,
+    /// Last tail call, which replaced the function of the frame.
+    tail_call: Option<CallSite>,
+    /// Calls of memoized functions, which end with the frame: the cache and the parameters of each call.
+    memos: Vec<MemoCall<S>>,
+    _call: Option<DepthGuard<'c>>,
+}
+
+struct Machine<'c, S: StdMod> {
+    context: &'c Rc<RunContext>,
+    stack: Vec<RefDataObj<S>>,
+    frames: Vec<Frame<'c, S>>,
+    /// Calls of memoized functions of the first frame, when the frame ended with a tail call returned to the caller.
+    memos: Vec<MemoCall<S>>,
+}
+
+impl<'c, S: StdMod> Machine<'c, S> {
+    fn execute(&mut self) -> Result<Flow<S>, RuntimeError> {
+        self.run_frames().map_err(|e| self.unwind(e))
+    }
+
+    /// Adds the locations and the calls of the frames to the error.
+    fn unwind(&mut self, mut error: RuntimeError) -> RuntimeError {
+        while let Some(frame) = self.frames.pop() {
+            if frame.ip > 0 {
+                error = error.at(frame.function.locations[frame.ip - 1]);
+            }
+            for site in frame.tail_call.iter().chain(&frame.call) {
+                error = error.called(&site.name.name(), site.location);
+            }
+        }
+        self.stack.clear();
+        error
+    }
+
+    fn frame(&mut self) -> &mut Frame<'c, S> {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn pop(&mut self) -> RefDataObj<S> {
+        self.stack.pop().unwrap()
+    }
+
+    /// Pushes the captured variables and `self_fn` of the function `f` starting in the last frame.
+    fn enter(&mut self, f: &S::FUNC, closure: &Closure<S>) -> Result<(), RuntimeError> {
+        self.stack.extend(closure.captures.iter().cloned());
+        if closure.function.uses_self {
+            let self_fn = self.context.alloc(S::func_obj(f.clone()))?;
+            self.stack.push(self_fn)
+        }
+        Ok(())
+    }
+
+    /// Finds the cached result of the memoized closure called with the parameters starting at `params` in the stack.
+    /// If there is none, the call is recorded in the last frame, so its result is cached when the frame ends.
+    fn cached(&mut self, closure: &Closure<S>, params: usize) -> Option<RefDataObj<S>> {
+        let memo = closure.memo.as_ref()?;
+        if let Some(value) = memo.get(&self.stack[params..]) {
+            return Some(value);
+        }
+        if memo.has_room(self.frame().memos.len()) {
+            let key = self.stack[params..].to_vec();
+            self.frame().memos.push((memo.clone(), key));
+        }
+        None
+    }
+
+    fn run_frames(&mut self) -> Result<Flow<S>, RuntimeError> {
+        let context = self.context;
+        loop {
+            let frame = self.frame();
+            let instruction = frame.function.code[frame.ip];
+            frame.ip += 1;
+            match instruction {
+                Instruction::Step => context.step()?,
+                Instruction::Constant(c) => {
+                    let value = match &self.frame().function.constants[c] {
+                        ConstantValue::Integer(v) => S::int_create(*v),
+                        ConstantValue::Float(v) => S::float_create(*v),
+                        ConstantValue::String(v) => S::string_create(v.clone())
+                    };
+                    self.stack.push(context.alloc(value)?)
+                }
+                Instruction::Local(slot) => {
+                    let base = self.frame().base;
+                    let value = self.stack[base + slot].clone();
+                    self.stack.push(value)
+                }
+                Instruction::Global { index, name } => {
+                    let frame = self.frame();
+                    let value = frame.globals[index].get().cloned()
+                        .ok_or_else(|| RuntimeError::from(RuntimeErrorKind::VariableNotFound { name: frame.function.names[name].to_string() }))?;
+                    self.stack.push(value)
+                }
+                Instruction::Operation(o) => {
+                    let rhs = self.pop();
+                    let lhs = self.pop();
+                    let value = calculate(lhs.borrow().deref(), &o, rhs.borrow().deref())?;
+                    self.stack.push(context.alloc(value)?)
+                }
+                Instruction::Tuple(n) => {
+                    let items = self.stack.drain(self.stack.len() - n..).map(|v| v.borrow().deref().clone()).collect();
+                    self.stack.push(context.alloc(DataObj::Tuple(items))?)
+                }
+                Instruction::Empty => self.stack.push(context.alloc(S::empty_create())?),
+                Instruction::Closure { function, captures } => {
+                    let captures = self.stack.drain(self.stack.len() - captures..).collect();
+                    let frame = self.frame();
+                    let function = frame.function.functions[function].clone();
+                    let memo = function.memo.then(|| context.memo());
+                    let closure = Closure { function, captures, globals: frame.globals.clone(), memo };
+                    self.stack.push(context.alloc(S::func_obj(S::FUNC::compiled(closure)))?)
+                }
+                Instruction::JumpIfFalse(target) => {
+                    let cond = self.pop();
+                    let cond = cond.borrow();
+                    match cond.deref() {
+                        DataObj::Bool(b) => if !b.is_true() { self.frame().ip = target },
+                        v => return Err(RuntimeErrorKind::ConditionNotBool { found: v.type_str().to_string() }.into())
+                    }
+                }
+                Instruction::Jump(target) => self.frame().ip = target,
+                Instruction::Builtin { builtin, params } => {
+                    let params: Vec<_> = self.stack.drain(self.stack.len() - params..).map(|v| v.borrow().deref().clone()).collect();
+                    let value = builtin.call(&params)?;
+                    self.stack.push(context.alloc(value)?)
+                }
+                Instruction::Call { call, params } => self.call(call, params)?,
+                Instruction::TailCall { call, params } => {
+                    if let Some(flow) = self.tail_call(call, params)? {
+                        return Ok(flow);
+                    }
+                }
+                Instruction::Return => {
+                    let value = self.pop();
+                    if let Some(value) = self.ret(value) {
+                        return Ok(Flow::Value(value));
+                    }
+                }
+                Instruction::Unimplemented(feature) => return Err(RuntimeErrorKind::Unimplemented { feature: feature.to_string() }.into()),
+            }
+        }
+    }
+
+    /// Ends the last frame with the value. Returns the value if it was the first frame of the machine.
+    fn ret(&mut self, value: RefDataObj<S>) -> Option<RefDataObj<S>> {
+        let frame = self.frames.pop().unwrap();
+        for (memo, params) in frame.memos {
+            memo.insert(params, value.clone())
+        }
+        self.stack.truncate(frame.start);
+        if self.frames.is_empty() {
+            return Some(value);
+        }
+        self.stack.push(value);
+        None
+    }
+
+    fn call(&mut self, call: usize, params: usize) -> Result<(), RuntimeError> {
+        let start = self.stack.len() - params - 1;
+        let callee = self.stack[start].clone();
+        let callee = callee.borrow();
+        let site = self.frame().function.calls[call].clone();
+        let f = match callee.deref() {
+            DataObj::Func(f) => f,
+            _ => return Err(not_function(&site))
+        };
+        let guard = self.context.enter_call().map_err(|e| e.at(site.location))?;
+        match f.as_compiled() {
+            Some(closure) => {
+                self.frames.push(Frame {
+                    function: closure.function.clone(),
+                    globals: closure.globals.clone(),
+                    start,
+                    base: start + 1,
+                    ip: 0,
+                    call: Some(site),
+                    tail_call: None,
+                    memos: Vec::new(),
+                    _call: Some(guard),
+                });
+                if let Some(value) = self.cached(closure, start + 1) {
+                    self.ret(value);
+                    return Ok(());
+                }
+                self.context.step()?;
+                self.enter(f, closure)
+            }
+            None => {
+                let params = self.stack.drain(start + 1..).collect();
+                let value = f.call(params, self.context.clone()).map_err(|e| e.called(&site.name.name(), site.location))?;
+                drop(guard);
+                self.stack.truncate(start);
+                self.stack.push(value);
+                Ok(())
+            }
+        }
+    }
+
+    /// Replaces the function of the last frame with the called one. Returns the result of the machine, if the called
+    /// function is not compiled, and it was run to the end, or the frame is the first one and the call is returned.
+    fn tail_call(&mut self, call: usize, params: usize) -> Result<Option<Flow<S>>, RuntimeError> {
+        let callee_index = self.stack.len() - params - 1;
+        let callee = self.stack[callee_index].clone();
+        let callee = callee.borrow();
+        let site = self.frame().function.calls[call].clone();
+        let f = match callee.deref() {
+            DataObj::Func(f) => f,
+            _ => return Err(not_function(&site))
+        };
+        let frame = self.frames.last_mut().unwrap();
+        let start = frame.start;
+        frame.tail_call = Some(site.clone());
+        frame.ip = 0;
+        self.stack.drain(start..callee_index);
+        match f.as_compiled() {
+            Some(closure) => {
+                let frame = self.frame();
+                frame.function = closure.function.clone();
+                frame.globals = closure.globals.clone();
+                frame.base = start + 1;
+                if let Some(value) = self.cached(closure, start + 1) {
+                    return Ok(self.ret(value).map(Flow::Value));
+                }
+                self.context.step()?;
+                self.enter(f, closure)?;
+                Ok(None)
+            }
+            // Only a machine started by `call` runs a function in its first frame. Its caller makes the call in a loop.
+            None if self.frames.len() == 1 => {
+                let params = self.stack.drain(start + 1..).collect();
+                let function = self.pop();
+                self.memos = self.frames.pop().unwrap().memos;
+                Ok(Some(Flow::TailCall { function, params, name: site.name, location: site.location }))
+            }
+            // The called function continues the chain of tail calls of the frame, which holds the call depth meanwhile.
+            None => {
+                let params = self.stack.drain(start + 1..).collect();
+                let frame = self.frame();
+                let memos = std::mem::take(&mut frame.memos);
+                frame.tail_call = None;
+                let value = f.tail_call(params, self.context.clone(), memos, site)?;
+                Ok(self.ret(value).map(Flow::Value))
+            }
+        }
+    }
+}
+
+fn not_function(site: &CallSite) -> RuntimeError {
+    RuntimeError::from(RuntimeErrorKind::NotFunction { name: site.name.to_string() }).at(site.location)
+}
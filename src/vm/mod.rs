@@ -0,0 +1,6 @@
+pub use crate::vm::compiler::compile;
+pub use crate::vm::function::{CallSite, Closure, Function, Instruction};
+
+pub mod compiler;
+pub mod function;
+pub(crate) mod machine;
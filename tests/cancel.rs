@@ -0,0 +1,56 @@
+use std::thread;
+use std::time::Duration;
+
+use berus_lang::error::Error;
+use berus_lang::run_env::{Backend, RunEnv};
+use berus_lang::run_env::error::RuntimeErrorKind;
+use berus_lang::std_library::StdModBasic;
+
+const BACKENDS: [Backend; 2] = [Backend::TreeWalker, Backend::Bytecode];
+
+const SPIN: &str = "pub let spin = <n:Int>:Int -> spin(n)
+pub let one = <>:Int -> 1
+";
+
+fn env(backend: Backend) -> RunEnv<StdModBasic> {
+    let mut env = RunEnv::new();
+    env.set_backend(backend);
+    env.parse_and_add("M".to_string(), SPIN).unwrap();
+    env
+}
+
+fn is_cancelled(result: Result<impl Sized, Error>) -> bool {
+    matches!(result, Err(Error::Runtime(e)) if e.kind == RuntimeErrorKind::Cancelled)
+}
+
+#[test]
+fn cancel_from_another_thread_stops_the_run() {
+    for backend in BACKENDS {
+        let env = env(backend);
+        let handle = env.cancel_handle();
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            handle.cancel()
+        });
+        assert!(is_cancelled(env.run("M::spin(1)")));
+        canceller.join().unwrap();
+        assert!(env.run("M::one()").is_ok());
+    }
+}
+
+#[test]
+fn cancel_made_before_the_run_starts_stops_it() {
+    for backend in BACKENDS {
+        let mut env = env(backend);
+        let handle = env.cancel_handle();
+        handle.cancel();
+        assert!(handle.is_cancelled());
+        assert!(is_cancelled(env.run("M::one()")));
+        assert!(!handle.is_cancelled());
+        assert!(env.run("M::one()").is_ok());
+
+        handle.cancel();
+        assert!(is_cancelled(env.parse_and_add("N".to_string(), "let x = M::one()")));
+        assert!(env.parse_and_add("N".to_string(), "let x = M::one()").is_ok());
+    }
+}
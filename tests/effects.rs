@@ -0,0 +1,85 @@
+use berus_lang::ast::effects::Effects;
+use berus_lang::error::Error;
+use berus_lang::run_env::{DataObj, RunEnv};
+use berus_lang::std_library::{IntType, StdModBasic};
+use berus_lang::verify::error::TypeErrorKind;
+
+fn env() -> RunEnv<StdModBasic> {
+    let mut env = RunEnv::new();
+    env.register_fn("log", "<Int>:Int", |params: &[DataObj<StdModBasic>]| match &params[0] {
+        DataObj::Int(v) => Ok(DataObj::Int(i32::create(v.to_i32()))),
+        _ => Err("not an Int".to_string())
+    }).unwrap();
+    env
+}
+
+fn effects(code: &str, names: &[&str]) -> Vec<Effects> {
+    let mut env = env();
+    env.parse_and_add("M".to_string(), code).unwrap();
+    let module = env.module("M").unwrap();
+    names.iter().map(|name| module.effects(name).unwrap_or_else(|| panic!("{} is not a lambda", name))).collect()
+}
+
+const MUTATION: Effects = Effects { mutation: true, host_calls: false, unknown_calls: false };
+
+#[test]
+fn effects_of_calls_are_inferred() {
+    let code = "pub let inc = <n:Int>:Int -> n + len(\"ab\")
+pub let logged = <n:Int>:Int -> log(inc(n))
+pub let apply = <f:<Int>:Int, n:Int>:Int -> f(n)
+pub let both = <n:Int>:Int -> apply(inc, logged(n))
+";
+    let found = effects(code, &["inc", "logged", "apply", "both"]);
+    assert_eq!(found, vec![Effects::PURE, Effects::HOST_CALL, Effects::UNKNOWN_CALL, Effects::HOST_CALL | Effects::UNKNOWN_CALL]);
+}
+
+#[test]
+fn mutable_capture_is_a_mutation() {
+    let code = "let mut total = 0
+pub let add = <n:Int> <mut total>:Int -> n + total
+pub let read = <n:Int> <total>:Int -> n + total
+pub let calls_add = <n:Int>:Int -> add(n)
+";
+    assert_eq!(effects(code, &["add", "read", "calls_add"]), vec![MUTATION, Effects::PURE, MUTATION]);
+}
+
+#[test]
+fn mutually_recursive_functions_share_their_effects() {
+    let code = "pub let is_even = <n:Int>:Bool -> if n < 1 n == 0 else is_odd(n - 1)
+pub let is_odd = <n:Int>:Bool -> if n < 1 n == 1 else is_even(log(n) - 1)
+pub let count = <n:Int>:Int -> if n < 1 0 else down(n - 1)
+pub let down = <n:Int>:Int -> count(n)
+";
+    let found = effects(code, &["is_even", "is_odd", "count", "down"]);
+    assert_eq!(found, vec![Effects::HOST_CALL, Effects::HOST_CALL, Effects::PURE, Effects::PURE]);
+}
+
+#[test]
+fn module_variables_without_lambdas_have_no_effects() {
+    let mut env = env();
+    env.parse_and_add("M".to_string(), "pub let x = 1\npub let f = <n:Int>:Int -> n").unwrap();
+    let module = env.module("M").unwrap();
+    assert_eq!(module.effects("x"), None);
+    assert_eq!(module.effects("missing"), None);
+}
+
+#[test]
+fn pure_function_with_effects_is_an_error() {
+    let codes = [
+        ("pub let f = pure <n:Int>:Int -> log(n)", Effects::HOST_CALL),
+        ("pub let f = pure <g:<Int>:Int>:Int -> g(1)", Effects::UNKNOWN_CALL),
+        ("let mut c = 1\npub let f = pure <n:Int> <mut c>:Int -> n + c", MUTATION),
+        ("pub let f = pure <n:Int>:Int -> if n < 1 0 else g(n - 1)\npub let g = <n:Int>:Int -> log(f(n))", Effects::HOST_CALL),
+    ];
+    for (code, expected) in codes {
+        match env().parse_and_add("M".to_string(), code) {
+            Err(Error::Type(e)) => {
+                assert_eq!(e.code(), "T0020");
+                assert_eq!(e.kind, TypeErrorKind::NotPure { effects: expected });
+            }
+            other => panic!("{}: {:?}", code, other.map(|_| ()))
+        }
+    }
+    let mut env = env();
+    env.parse_and_add("M".to_string(), "pub let f = pure <n:Int>:Int -> if n < 1 0 else f(n - 1) + 1").unwrap();
+}
@@ -0,0 +1,61 @@
+use berus_lang::error::Error;
+use berus_lang::run_env::{Backend, RunEnv};
+use berus_lang::run_env::error::RuntimeErrorKind;
+use berus_lang::std_library::StdModBasic;
+
+const BACKENDS: [Backend; 2] = [Backend::TreeWalker, Backend::Bytecode];
+
+const CODE: &str = "pub let inc = <n:Int>:Int -> n + 1
+pub let down = <n:Int>:Int -> if n < 1 0 else down(n - 1)
+";
+
+fn env(backend: Backend) -> RunEnv<StdModBasic> {
+    let mut env = RunEnv::new();
+    env.set_backend(backend);
+    env.parse_and_add("M".to_string(), CODE).unwrap();
+    env
+}
+
+#[test]
+fn fuel_used_counts_the_steps_of_the_last_run() {
+    for backend in BACKENDS {
+        let env = env(backend);
+        let expected = [("1", 1), ("1 + 2", 1), ("M::inc(1)", 4), ("M::inc(M::inc(1))", 7)];
+        for (expr, fuel) in expected {
+            env.run(expr).unwrap();
+            assert_eq!(env.fuel_used(), fuel, "{:?}: {}", backend, expr);
+        }
+    }
+}
+
+#[test]
+fn run_stops_when_out_of_fuel() {
+    for backend in BACKENDS {
+        let mut env = env(backend);
+        env.set_fuel(Some(7));
+        assert!(env.run("M::inc(M::inc(1))").is_ok());
+        env.set_fuel(Some(6));
+        match env.run("M::inc(M::inc(1))") {
+            Err(Error::Runtime(e)) => {
+                assert_eq!(e.code(), "R0010");
+                assert_eq!(e.kind, RuntimeErrorKind::OutOfFuel { fuel: 6 });
+            }
+            other => panic!("{:?}: {:?}", backend, other.map(|_| ()))
+        }
+        assert_eq!(env.fuel_used(), 6, "{:?}", backend);
+    }
+}
+
+#[test]
+fn fuel_stops_unbounded_loops() {
+    for backend in BACKENDS {
+        let mut env = env(backend);
+        env.set_fuel(Some(1000));
+        let error = env.run("M::down(1000000)").err().unwrap();
+        assert_eq!(error.code(), "R0010", "{:?}", backend);
+
+        env.set_fuel(None);
+        assert!(env.run("M::down(1000)").is_ok());
+        assert!(env.fuel_used() > 1000, "{:?}", backend);
+    }
+}
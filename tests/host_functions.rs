@@ -0,0 +1,45 @@
+use std::ops::Deref;
+
+use berus_lang::error::Error;
+use berus_lang::run_env::{Backend, DataObj, RunEnv};
+use berus_lang::run_env::error::RuntimeErrorKind;
+use berus_lang::std_library::{IntType, StdModBasic};
+
+const BACKENDS: [Backend; 2] = [Backend::TreeWalker, Backend::Bytecode];
+
+fn env(backend: Backend) -> RunEnv<StdModBasic> {
+    let mut env = RunEnv::new();
+    env.set_backend(backend);
+    env.register_fn("double", "<Int>:Int", |params: &[DataObj<StdModBasic>]| match &params[0] {
+        DataObj::Int(v) => Ok(DataObj::Int(i32::create(v.to_i32() * 2))),
+        _ => Err("not an Int".to_string())
+    }).unwrap();
+    env.register_fn("bad", "<Int>:String", |_| Ok(DataObj::Int(i32::create(1)))).unwrap();
+    env.register_fn("pair", "<>:(Int,Int)", |_| Ok(DataObj::Tuple(vec![DataObj::Int(i32::create(1)), DataObj::Int(i32::create(2))]))).unwrap();
+    env
+}
+
+#[test]
+fn host_function_is_called_with_the_arguments() {
+    for backend in BACKENDS {
+        let (_, value) = env(backend).run("double(21)").unwrap();
+        match value.borrow().deref() {
+            DataObj::Int(v) => assert_eq!(v.to_i32(), 42),
+            _ => panic!("double(21) is not an Int")
+        };
+        assert!(env(backend).run("pair()").is_ok());
+    }
+}
+
+#[test]
+fn result_of_wrong_type_is_reported_at_the_call_of_the_host_function() {
+    for backend in BACKENDS {
+        match env(backend).run("len(bad(1))") {
+            Err(Error::Runtime(e)) => {
+                assert_eq!(e.kind, RuntimeErrorKind::HostFunction { function: "bad".to_string(), message: "returned INT instead of String".to_string() });
+                assert_eq!(e.location.map(|l| l.column), Some(5));
+            }
+            other => panic!("{:?}", other.map(|_| ()))
+        }
+    }
+}
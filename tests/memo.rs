@@ -0,0 +1,80 @@
+use std::ops::Deref;
+
+use berus_lang::error::Error;
+use berus_lang::run_env::{Backend, DataObj, RunEnv};
+use berus_lang::std_library::{IntType, StdModBasic};
+use berus_lang::verify::error::TypeErrorKind;
+
+const BACKENDS: [Backend; 2] = [Backend::TreeWalker, Backend::Bytecode];
+
+const FIB: &str = "pub let fib = <n:Int>:Int -> if n < 2 n else fib(n - 1) + fib(n - 2)
+pub let mfib = memo <n:Int>:Int -> if n < 2 n else mfib(n - 1) + mfib(n - 2)
+";
+
+fn env(backend: Backend, memo_capacity: Option<usize>) -> RunEnv<StdModBasic> {
+    let mut env = RunEnv::new();
+    env.set_backend(backend);
+    if let Some(capacity) = memo_capacity {
+        env.set_memo_capacity(capacity);
+    }
+    env.parse_and_add("M".to_string(), FIB).unwrap();
+    env
+}
+
+/// Result of the expression and the fuel it used.
+fn run_int(env: &RunEnv<StdModBasic>, expr: &str) -> (i32, u64) {
+    let (_, value) = env.run(expr).unwrap();
+    let value = value.borrow();
+    match value.deref() {
+        DataObj::Int(v) => (v.to_i32(), env.fuel_used()),
+        _ => panic!("{} is not an Int", expr)
+    }
+}
+
+#[test]
+fn cached_results_save_fuel() {
+    for backend in BACKENDS {
+        let env = env(backend, None);
+        let (plain, plain_fuel) = run_int(&env, "M::fib(20)");
+        let (memo, memo_fuel) = run_int(&env, "M::mfib(20)");
+        assert_eq!(plain, 6765);
+        assert_eq!(memo, plain);
+        assert!(memo_fuel * 100 < plain_fuel, "{:?}: {} and {}", backend, memo_fuel, plain_fuel);
+
+        // The cache of a module variable is kept between the executions.
+        let (again, again_fuel) = run_int(&env, "M::mfib(20)");
+        assert_eq!(again, plain);
+        assert!(again_fuel < memo_fuel, "{:?}: {} and {}", backend, again_fuel, memo_fuel);
+    }
+}
+
+#[test]
+fn results_over_the_capacity_are_not_cached() {
+    for backend in BACKENDS {
+        let (_, plain_fuel) = run_int(&env(backend, None), "M::fib(16)");
+        let (_, full_fuel) = run_int(&env(backend, None), "M::mfib(16)");
+        let (value, empty_fuel) = run_int(&env(backend, Some(0)), "M::mfib(16)");
+        let (_, small_fuel) = run_int(&env(backend, Some(4)), "M::mfib(16)");
+        assert_eq!(value, 987);
+        assert_eq!(empty_fuel, plain_fuel, "{:?}", backend);
+        assert!(full_fuel < small_fuel && small_fuel < empty_fuel, "{:?}: {} {} {}", backend, full_fuel, small_fuel, empty_fuel);
+    }
+}
+
+#[test]
+fn memoized_function_cannot_use_mutable_variables() {
+    let codes = [
+        ("pub let f = memo <n:mut Int>:Int -> n + 1", "n"),
+        ("let mut c = 1\npub let f = memo <n:Int> <mut c>:Int -> n + c", "c"),
+    ];
+    for (code, name) in codes {
+        let mut env: RunEnv<StdModBasic> = RunEnv::new();
+        match env.parse_and_add("M".to_string(), code) {
+            Err(Error::Type(e)) => {
+                assert_eq!(e.code(), "T0019");
+                assert_eq!(e.kind, TypeErrorKind::MutableMemo { name: name.to_string() });
+            }
+            other => panic!("{}: {:?}", code, other.map(|_| ()))
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use berus_lang::error::Error;
+use berus_lang::run_env::{Backend, RunEnv};
+use berus_lang::run_env::error::RuntimeErrorKind;
+use berus_lang::std_library::StdModBasic;
+
+const BACKENDS: [Backend; 2] = [Backend::TreeWalker, Backend::Bytecode];
+
+const CODE: &str = "pub let text = \"some text kept by the module\"
+pub let grow = <s:String, n:Int>:String -> if n < 1 s else grow(s + s, n - 1)
+";
+
+fn env(backend: Backend) -> RunEnv<StdModBasic> {
+    let mut env = RunEnv::new();
+    env.set_backend(backend);
+    env.parse_and_add("M".to_string(), CODE).unwrap();
+    env
+}
+
+#[test]
+fn values_of_the_modules_stay_in_memory() {
+    for backend in BACKENDS {
+        let env = env(backend);
+        let module_memory = env.memory_used();
+        assert!(module_memory > 0, "{:?}", backend);
+
+        let (_, value) = env.run("M::grow(M::text, 4)").unwrap();
+        assert!(env.memory_used() > module_memory, "{:?}", backend);
+        drop(value);
+        assert_eq!(env.memory_used(), module_memory, "{:?}", backend);
+    }
+}
+
+#[test]
+fn run_stops_when_out_of_memory() {
+    for backend in BACKENDS {
+        let mut env = env(backend);
+        let module_memory = env.memory_used();
+        env.set_memory_limit(Some(module_memory + 10_000));
+        match env.run("M::grow(M::text, 20)") {
+            Err(Error::Runtime(e)) => {
+                assert_eq!(e.code(), "R0011");
+                assert_eq!(e.kind, RuntimeErrorKind::OutOfMemory { limit: module_memory + 10_000 });
+            }
+            other => panic!("{:?}: {:?}", backend, other.map(|_| ()))
+        }
+        assert_eq!(env.memory_used(), module_memory, "{:?}", backend);
+
+        assert!(env.run("M::grow(M::text, 4)").is_ok());
+        env.set_memory_limit(None);
+        assert!(env.run("M::grow(M::text, 12)").is_ok());
+        assert_eq!(env.memory_used(), module_memory, "{:?}", backend);
+    }
+}
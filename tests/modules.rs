@@ -0,0 +1,82 @@
+use std::ops::Deref;
+
+use berus_lang::error::Error;
+use berus_lang::run_env::{DataObj, RunEnv};
+use berus_lang::std_library::{IntType, StdModBasic};
+use berus_lang::verify::error::TypeErrorKind;
+
+const A: &str = "pub let f = <n:Int>:Int -> n + 1
+pub let g = <n:Int>:Int -> n + n
+pub let name = 1
+let hidden = 5
+";
+
+fn env_with_a() -> RunEnv<StdModBasic> {
+    let mut env = RunEnv::new();
+    env.parse_and_add("A".to_string(), A).unwrap();
+    env
+}
+
+fn run_int(env: &RunEnv<StdModBasic>, expr: &str) -> i32 {
+    let (_, value) = env.run(expr).unwrap();
+    let value = value.borrow();
+    match value.deref() {
+        DataObj::Int(v) => v.to_i32(),
+        _ => panic!("{} is not an Int", expr)
+    }
+}
+
+fn type_error(env: &mut RunEnv<StdModBasic>, code: &str) -> (String, TypeErrorKind) {
+    match env.parse_and_add("B".to_string(), code) {
+        Err(Error::Type(e)) => (e.code().to_string(), e.kind),
+        other => panic!("{}: {:?}", code, other.map(|_| ()))
+    }
+}
+
+#[test]
+fn imported_names_can_be_used_unqualified() {
+    let mut env = env_with_a();
+    env.parse_and_add("B".to_string(), "import A.{f, g}\npub let x = g(f(3))").unwrap();
+    assert_eq!(run_int(&env, "B::x"), 8);
+}
+
+#[test]
+fn qualified_names_need_no_import() {
+    let mut env = env_with_a();
+    env.parse_and_add("B".to_string(), "pub let x = A::g(A::f(3))").unwrap();
+    assert_eq!(run_int(&env, "B::x"), 8);
+    assert_eq!(run_int(&env, "A::f(B::x)"), 9);
+}
+
+#[test]
+fn modules_have_separate_namespaces() {
+    let mut env = env_with_a();
+    env.parse_and_add("B".to_string(), "pub let name = 10\npub let f = <n:Int>:Int -> n - 1").unwrap();
+    assert_eq!(run_int(&env, "A::name"), 1);
+    assert_eq!(run_int(&env, "B::name"), 10);
+    assert_eq!(run_int(&env, "A::f(5) + B::f(5)"), 10);
+}
+
+#[test]
+fn imported_name_defined_in_the_module_is_a_conflict() {
+    let mut env = env_with_a();
+    let (code, kind) = type_error(&mut env, "import A.{f}\npub let f = 3");
+    assert_eq!(code, "T0006");
+    assert_eq!(kind, TypeErrorKind::ImportConflict { name: "f".to_string() });
+    assert!(env.module("B").is_none());
+}
+
+#[test]
+fn import_from_unknown_module_is_an_error() {
+    let mut env = env_with_a();
+    let (code, kind) = type_error(&mut env, "import C.{f}\npub let x = f(1)");
+    assert_eq!(code, "T0002");
+    assert_eq!(kind, TypeErrorKind::UnknownModule { module: "C".to_string() });
+}
+
+#[test]
+fn private_names_cannot_be_imported() {
+    let mut env = env_with_a();
+    let (_, kind) = type_error(&mut env, "import A.hidden\npub let x = hidden");
+    assert_eq!(kind, TypeErrorKind::PrivateVariable { module: "A".to_string(), name: "hidden".to_string() });
+}
@@ -0,0 +1,36 @@
+use std::ops::Deref;
+
+use berus_lang::run_env::{DataObj, RunEnv};
+use berus_lang::std_library::{IntType, StdMod, StdModBasic, StdModSaturating, StdModWrapping};
+
+const OVERFLOW: &str = "pub let max = 2147483647
+pub let over = <>:Int -> if max + 1 < 0 1 else 2
+pub let under = <>:Int -> if 0 - max - 2 < 0 1 else 2
+";
+
+fn run<S: StdMod>(optimize: bool, expr: &str) -> (Result<i32, String>, u64) {
+    let mut env: RunEnv<S> = RunEnv::new();
+    env.set_optimize(optimize);
+    env.parse_and_add("M".to_string(), OVERFLOW).unwrap();
+    let value = env.run(expr).map(|(_, v)| match v.borrow().deref() {
+        DataObj::Int(v) => v.to_i32(),
+        _ => panic!("{} is not an Int", expr)
+    });
+    (value.map_err(|e| e.to_string()), env.fuel_used())
+}
+
+#[test]
+fn constants_are_folded_with_the_std_library_of_the_env() {
+    for expr in ["M::over()", "M::under()"] {
+        assert!(run::<StdModBasic>(true, expr).0.is_err());
+        assert_eq!(run::<StdModBasic>(true, expr), run::<StdModBasic>(false, expr));
+        for (optimized, plain) in [
+            (run::<StdModWrapping>(true, expr), run::<StdModWrapping>(false, expr)),
+            (run::<StdModSaturating>(true, expr), run::<StdModSaturating>(false, expr)),
+        ] {
+            assert_eq!(optimized.0, plain.0, "{}", expr);
+            // The condition with the overflowing operation is folded, so the optimized module uses less fuel.
+            assert!(optimized.1 < plain.1, "{}", expr);
+        }
+    }
+}
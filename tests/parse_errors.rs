@@ -0,0 +1,39 @@
+use std::ops::Deref;
+
+use berus_lang::error::Error;
+use berus_lang::parser::error::ParseErrorKind;
+use berus_lang::run_env::{DataObj, RunEnv};
+use berus_lang::std_library::{IntType, StdModBasic};
+
+fn add(code: &str) -> Result<RunEnv<StdModBasic>, Error> {
+    let mut env = RunEnv::new();
+    env.parse_and_add("M".to_string(), code)?;
+    Ok(env)
+}
+
+#[test]
+fn integer_literal_out_of_range_is_an_error() {
+    for (code, literal) in [("pub let big = 2147483648", "2147483648"), ("pub let small =   -2147483649", "-2147483649")] {
+        match add(code) {
+            Err(Error::Parse(e)) => {
+                assert_eq!(e.code(), "P0003");
+                assert_eq!(e.kind, ParseErrorKind::LiteralOutOfRange { literal: literal.to_string() });
+                assert_eq!(e.location.line, 1);
+                assert_eq!(e.location.column, code.find(literal).unwrap() + 1);
+            }
+            other => panic!("{} was parsed into {:?}", code, other.map(|_| ()))
+        }
+    }
+}
+
+#[test]
+fn integer_literals_at_the_limits_are_parsed() {
+    let env = add("pub let max = 2147483647\npub let min = -2147483648").unwrap();
+    for (name, expected) in [("M::max", i32::MAX), ("M::min", i32::MIN)] {
+        let (_, value) = env.run(name).unwrap();
+        match value.borrow().deref() {
+            DataObj::Int(v) => assert_eq!(v.to_i32(), expected),
+            _ => panic!("{} is not an Int", name)
+        };
+    }
+}
@@ -0,0 +1,77 @@
+use std::ops::Deref;
+
+use berus_lang::error::Error;
+use berus_lang::run_env::{Backend, DataObj, RunEnv};
+use berus_lang::std_library::{IntType, StdModBasic};
+use berus_lang::verify::error::TypeErrorKind;
+
+const BACKENDS: [Backend; 2] = [Backend::TreeWalker, Backend::Bytecode];
+
+fn add(backend: Backend, code: &str) -> Result<RunEnv<StdModBasic>, Error> {
+    let mut env = RunEnv::new();
+    env.set_backend(backend);
+    env.parse_and_add("M".to_string(), code)?;
+    Ok(env)
+}
+
+fn run_int(env: &RunEnv<StdModBasic>, expr: &str) -> i32 {
+    let (_, value) = env.run(expr).unwrap();
+    let value = value.borrow();
+    match value.deref() {
+        DataObj::Int(v) => v.to_i32(),
+        _ => panic!("{} is not an Int", expr)
+    }
+}
+
+#[test]
+fn function_can_call_itself_by_name() {
+    for backend in BACKENDS {
+        let env = add(backend, "pub let fib = <n:Int>:Int -> if n < 2 n else fib(n - 1) + fib(n - 2)").unwrap();
+        assert_eq!(run_int(&env, "M::fib(15)"), 610);
+    }
+}
+
+#[test]
+fn functions_can_call_each_other() {
+    for backend in BACKENDS {
+        let env = add(backend, "pub let is_even = <n:Int>:Bool -> if n < 1 n == 0 else is_odd(n - 1)
+pub let is_odd = <n:Int>:Bool -> if n < 1 n == 1 else is_even(n - 1)
+pub let parity = <n:Int>:Int -> if is_even(n) 0 else 1
+").unwrap();
+        assert_eq!(run_int(&env, "M::parity(10)"), 0);
+        assert_eq!(run_int(&env, "M::parity(7)"), 1);
+    }
+}
+
+#[test]
+fn variables_can_be_used_before_their_definition() {
+    for backend in BACKENDS {
+        let env = add(backend, "pub let x = y + 1
+pub let z = double(x)
+pub let double = <n:Int>:Int -> n + n
+pub let y = 20
+").unwrap();
+        assert_eq!(run_int(&env, "M::x"), 21);
+        assert_eq!(run_int(&env, "M::z"), 42);
+    }
+}
+
+#[test]
+fn value_depending_on_itself_is_an_error() {
+    for code in ["pub let x = x + 1", "pub let x = y + 1\npub let y = x"] {
+        match add(Backend::TreeWalker, code) {
+            Err(Error::Type(e)) => {
+                assert_eq!(e.code(), "T0005");
+                assert_eq!(e.kind, TypeErrorKind::RecursiveValue { name: "x".to_string() });
+                assert_eq!(e.location.map(|l| l.line), Some(1));
+            }
+            other => panic!("{}: {:?}", code, other.map(|_| ()))
+        }
+    }
+}
+
+#[test]
+fn value_can_depend_on_a_recursive_function() {
+    let env = add(Backend::TreeWalker, "pub let x = f(3)\npub let f = <n:Int>:Int -> if n < 1 0 else 2 + f(n - 1)").unwrap();
+    assert_eq!(run_int(&env, "M::x"), 6);
+}
@@ -0,0 +1,40 @@
+use std::thread;
+
+use berus_lang::error::Error;
+use berus_lang::run_env::RunEnv;
+use berus_lang::std_library::StdModBasic;
+
+/// Size of the native stack of a standard main thread.
+const MAIN_THREAD_STACK: usize = 8 * 1024 * 1024;
+
+/// Recursive function, whose recursive call is inside `nesting` nested if expressions.
+fn nested_recursion(nesting: usize) -> String {
+    let ifs: String = (0..nesting).map(|i| format!("if n < -{} 0 else ", i + 1)).collect();
+    format!("pub let f = <n:Int>:Int -> if n < 1 0 else {}1 + f(n - 1)", ifs)
+}
+
+/// Runs `M::f(calls)` with the default limits on a thread with the stack of a main thread.
+fn run_on_main_thread_stack(code: String, calls: i32) -> Result<(), String> {
+    thread::Builder::new().stack_size(MAIN_THREAD_STACK).spawn(move || {
+        let mut env: RunEnv<StdModBasic> = RunEnv::new();
+        env.parse_and_add("M".to_string(), &code).unwrap();
+        env.run(&format!("M::f({})", calls)).map(|_| ()).map_err(|e| match e {
+            Error::Runtime(e) => e.code().to_string(),
+            e => panic!("{}", e)
+        })
+    }).unwrap().join().unwrap()
+}
+
+#[test]
+fn deeply_nested_expressions_stop_with_an_error() {
+    assert_eq!(run_on_main_thread_stack(nested_recursion(60), 250), Err("R0015".to_string()));
+}
+
+#[test]
+fn default_limits_fit_in_the_stack_of_a_main_thread() {
+    assert_eq!(run_on_main_thread_stack(nested_recursion(0), 250), Ok(()));
+    for nesting in [1, 2, 4, 6, 8, 12, 16, 30] {
+        let result = run_on_main_thread_stack(nested_recursion(nesting), 300);
+        assert!(matches!(result.as_ref().map_err(String::as_str), Err("R0009" | "R0015")), "{}: {:?}", nesting, result);
+    }
+}
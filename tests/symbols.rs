@@ -0,0 +1,21 @@
+use std::ops::Deref;
+
+use berus_lang::parser::parse;
+use berus_lang::run_env::{DataObj, RunEnv};
+use berus_lang::std_library::{IntType, StdModBasic};
+
+#[test]
+fn module_parsed_with_its_own_symbol_table_can_be_added() {
+    let mut env: RunEnv<StdModBasic> = RunEnv::new();
+    env.parse_and_add("A".to_string(), "pub let inc = <n:Int>:Int -> n + 1").unwrap();
+    let module = parse("import A.inc\npub let two = inc(A::inc(0))").unwrap();
+    env.add_module("B".to_string(), module).unwrap();
+
+    let (_, value) = env.run("B::two + A::inc(B::two)").unwrap();
+    match value.borrow().deref() {
+        DataObj::Int(v) => assert_eq!(v.to_i32(), 5),
+        _ => panic!("not an Int")
+    };
+    assert!(env.find_variable("B::two").is_some());
+    assert!(env.find_variable("B::never_interned").is_none());
+}
@@ -0,0 +1,105 @@
+use std::ops::Deref;
+
+use berus_lang::run_env::{Backend, DataObj, RunEnv};
+use berus_lang::std_library::{IntType, StdModBasic};
+
+const BACKENDS: [Backend; 2] = [Backend::TreeWalker, Backend::Bytecode];
+
+const LOOPS: &str = "pub let count = <n:Int, acc:Int>:Int -> if n < 1 acc else count(n - 1, acc + 1)
+pub let even = <n:Int>:Int -> if n < 1 1 else odd(n - 1)
+pub let odd = <n:Int>:Int -> if n < 1 0 else even(n - 1)
+pub let down = <n:Int>:Int -> if n < 1 0 else self_fn(n - 1)
+pub let sum = <n:Int>:Int -> if n < 1 0 else n + sum(n - 1)
+";
+
+fn env(backend: Backend, code: &str) -> RunEnv<StdModBasic> {
+    let mut env = RunEnv::new();
+    env.set_backend(backend);
+    env.parse_and_add("M".to_string(), code).unwrap();
+    env
+}
+
+fn run_int(env: &RunEnv<StdModBasic>, expr: &str) -> i32 {
+    let (_, value) = env.run(expr).unwrap();
+    let value = value.borrow();
+    match value.deref() {
+        DataObj::Int(v) => v.to_i32(),
+        _ => panic!("{} is not an Int", expr)
+    }
+}
+
+#[test]
+fn display_marks_calls_in_tail_position() {
+    let m = env(Backend::TreeWalker, LOOPS).module("M").unwrap().to_string();
+    assert!(m.contains("TAILCALL count("), "{}", m);
+    assert!(m.contains("TAILCALL odd("), "{}", m);
+    assert!(m.contains("TAILCALL even("), "{}", m);
+    assert!(m.contains("TAILCALL self_fn("), "{}", m);
+    // The result of the call is added to, so the call is not the last evaluated expression.
+    assert!(m.contains("Plus CALL sum("), "{}", m);
+    assert!(!m.contains("TAILCALL sum("), "{}", m);
+}
+
+#[test]
+fn deep_tail_recursion_runs_on_both_backends() {
+    for backend in BACKENDS {
+        let env = env(backend, LOOPS);
+        assert_eq!(run_int(&env, "M::count(1000000, 0)"), 1000000, "{:?}", backend);
+        assert_eq!(run_int(&env, "M::even(1000000)"), 1, "{:?}", backend);
+        assert_eq!(run_int(&env, "M::down(1000000)"), 0, "{:?}", backend);
+    }
+}
+
+#[test]
+fn non_tail_recursion_is_limited_on_both_backends() {
+    for backend in BACKENDS {
+        let mut env = env(backend, LOOPS);
+        // Test threads have small native stacks, which the tree-walker uses for calls.
+        env.set_max_call_depth(64);
+        let error = env.run("M::sum(1000000)").err().unwrap();
+        assert_eq!(error.code(), "R0009", "{:?}", backend);
+    }
+}
+
+const APPLY: &str = "pub let apply = <f:<Int>:Int, n:Int>:Int -> f(n)\n";
+
+const LOOP_THROUGH_APPLY: &str = "import A.{apply}
+pub let lp = <n:Int>:Int -> if n < 1 0 else apply(lp, n - 1)
+pub let mem = memo <n:Int>:Int -> if n < 1 7 else apply(mem, n - 1)
+pub let fail = <n:Int>:Int -> if n < 1 len(substring(\"ab\", 0, 5)) else apply(fail, n - 1)
+";
+
+/// Runs the expressions with the modules and the expressions run by the given backends.
+fn run_mixed(a: Backend, b: Backend, run: Backend, exprs: &[&str]) -> Vec<(Result<i32, String>, u64)> {
+    let mut env: RunEnv<StdModBasic> = RunEnv::new();
+    env.set_backend(a);
+    env.parse_and_add("A".to_string(), APPLY).unwrap();
+    env.set_backend(b);
+    env.parse_and_add("B".to_string(), LOOP_THROUGH_APPLY).unwrap();
+    env.set_backend(run);
+    exprs.iter().map(|e| {
+        let value = env.run(e).map(|(_, v)| match v.borrow().deref() {
+            DataObj::Int(v) => v.to_i32(),
+            _ => panic!("{} is not an Int", e)
+        });
+        (value.map_err(|e| e.to_string()), env.fuel_used())
+    }).collect()
+}
+
+#[test]
+fn tail_calls_between_backends_match() {
+    let exprs = ["B::lp(100000)", "B::mem(100000)", "B::mem(100000)", "B::fail(3)"];
+    let expected = run_mixed(Backend::TreeWalker, Backend::TreeWalker, Backend::TreeWalker, &exprs);
+    assert_eq!(expected[0].0, Ok(0));
+    assert_eq!(expected[1].0, Ok(7));
+    // The second call finds the result in the cache of `mem`.
+    assert!(expected[2].1 < expected[1].1);
+    assert!(expected[3].0.is_err());
+    for a in BACKENDS {
+        for b in BACKENDS {
+            for run in BACKENDS {
+                assert_eq!(run_mixed(a, b, run, &exprs), expected, "A:{:?} B:{:?} run:{:?}", a, b, run);
+            }
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use berus_lang::error::Error;
+use berus_lang::run_env::RunEnv;
+use berus_lang::std_library::StdModBasic;
+
+fn add(code: &str) -> Result<RunEnv<StdModBasic>, Error> {
+    let mut env = RunEnv::new();
+    env.parse_and_add("M".to_string(), code)?;
+    Ok(env)
+}
+
+fn type_of(env: &RunEnv<StdModBasic>, name: &str) -> String {
+    let v = env.module("M").unwrap().variables.iter().find(|v| v.name.to_string() == name).unwrap();
+    v.variable.v_type.to_string()
+}
+
+fn error(code: &str) -> Error {
+    match add(code) {
+        Ok(_) => panic!("{} was verified", code),
+        Err(e) => e
+    }
+}
+
+#[test]
+fn types_of_parameters_are_inferred_from_their_use() {
+    let env = add("pub let inc = <a> -> a + 1
+pub let k = <x, y> -> if x == y 1 else x
+pub let twice = <f, x> -> f(f(x))
+").unwrap();
+    assert_eq!(type_of(&env, "inc"), "<Int>:Int");
+    assert_eq!(type_of(&env, "k"), "<Int,Int>:Int");
+    assert_eq!(type_of(&env, "twice"), "<<'a>:'a,'a>:'a");
+}
+
+#[test]
+fn generic_function_can_be_used_at_different_types() {
+    let env = add("pub let id = <x> -> x
+pub let a = id(1)
+pub let s = id(\"s\")
+pub let f = id(<n:Int>:Int -> n)
+").unwrap();
+    assert_eq!(type_of(&env, "id"), "<'a>:'a");
+    assert_eq!(type_of(&env, "a"), "Int");
+    assert_eq!(type_of(&env, "s"), "String");
+    assert_eq!(type_of(&env, "f"), "<Int>:Int");
+    let (t, _) = env.run("M::id(M::s)").unwrap();
+    assert_eq!(t.to_string(), "String");
+}
+
+#[test]
+fn occurs_check_rejects_infinite_types() {
+    let e = error("pub let twice = <f, x> -> f(f(x))\npub let id = <x> -> twice(<y> -> y, x)\npub let f = <x> -> x(x)");
+    assert_eq!(e.code(), "T0008");
+    assert_eq!(e.to_string(), "[T0008] Infinite type. expected:'a, found:<'a>:'b at 3:22");
+}
+
+#[test]
+fn mismatch_is_reported_with_both_types() {
+    let e = error("pub let f = <x:Int>:Int -> x\npub let y = f(\"a\")");
+    assert_eq!(e.code(), "T0007");
+    assert_eq!(e.to_string(), "[T0007] Wrong type. expected:Int, found:String at 2:15");
+}
+
+#[test]
+fn type_variables_of_errors_are_numbered_from_the_start() {
+    let env = add("pub let x = 1").unwrap();
+    match env.run("<a, b, c> -> if a(b) c(c) else b") {
+        Err(e) => assert_eq!(e.to_string(), "[T0008] Infinite type. expected:'a, found:<'a>:'b at 1:24"),
+        Ok(_) => panic!("infinite type was verified")
+    }
+}